@@ -0,0 +1,420 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::lexer::PDFProcessingError;
+use crate::object::{Dictionary, Object, ObjectRef, Reference};
+use crate::{Warning, PDF};
+
+/// The version of PDF a document conforms to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl Version {
+    pub fn new(major: u8, minor: u8) -> Version {
+        return Version { major, minor };
+    }
+
+    /// Parses `major.minor`, as found in the header and the catalog's `/Version`
+    pub fn parse(bytes: &[u8]) -> Option<Version> {
+        let text = std::str::from_utf8(bytes).ok()?;
+        let (major, minor) = text.split_once('.')?;
+        if major.is_empty() || minor.is_empty() {
+            return None;
+        }
+        return Some(Version::new(major.parse().ok()?, minor.parse().ok()?));
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Options for opening a [`Document`], built like [`std::fs::OpenOptions`]
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+    repair: bool,
+}
+
+impl OpenOptions {
+    pub fn new() -> OpenOptions {
+        return OpenOptions::default();
+    }
+
+    /// Whether to rebuild the cross-reference table by scanning the file when the header,
+    /// `startxref` or cross-reference sections cannot be read, or the catalog cannot be resolved
+    /// through them, rather than failing
+    pub fn repair(&mut self, repair: bool) -> &mut OpenOptions {
+        self.repair = repair;
+        return self;
+    }
+
+    pub fn open_bytes(&self, buf: Vec<u8>) -> Result<Document, PDFProcessingError> {
+        let mut pdf = PDF::new();
+        let (header_offset, header_version) = match pdf.parse_header(&buf) {
+            Ok(header) => header,
+            Err(err) if self.repair => {
+                pdf.warn(0, format!("{}, assuming version 1.0", err));
+                (0, Version::new(1, 0))
+            }
+            Err(err) => return Err(err),
+        };
+
+        let sections = pdf
+            .locate_xref(&buf, header_offset)
+            .and_then(|offset| pdf.parse_xref(&buf, offset))
+            .and_then(|(xref, trailer)| {
+                pdf.xref = xref;
+                // A table that parses may still hold the wrong offsets
+                if self.repair {
+                    check_catalog(&mut pdf, &buf, &trailer)?;
+                }
+                return Ok(trailer);
+            });
+        let (trailer, repaired) = match sections {
+            Ok(trailer) => (trailer, false),
+            Err(err) if self.repair => {
+                pdf.warn(0, format!("Repairing the file: {}", err));
+                let (xref, trailer) = pdf.reconstruct_xref(&buf)?;
+                pdf.xref = xref;
+                (trailer, true)
+            }
+            Err(err) => return Err(err),
+        };
+
+        return Ok(Document {
+            buf,
+            pdf: RefCell::new(pdf),
+            trailer,
+            objects: RefCell::new(HashMap::new()),
+            header_version,
+            repaired,
+        });
+    }
+
+    pub fn open(&self, path: impl AsRef<Path>) -> Result<Document, PDFProcessingError> {
+        let buf = std::fs::read(path)?;
+        return self.open_bytes(buf);
+    }
+}
+
+/// Checks that the trailer's `/Root` resolves to a `/Type /Catalog` dictionary
+fn check_catalog(
+    pdf: &mut PDF,
+    buf: &[u8],
+    trailer: &Dictionary,
+) -> Result<(), PDFProcessingError> {
+    let root = trailer
+        .get_ref("Root")
+        .ok_or_else(|| PDFProcessingError::General("Trailer is missing /Root".to_string()))?;
+    let catalog = pdf.resolve(buf, root)?;
+    if catalog
+        .as_dict()
+        .and_then(|dict| dict.get_name("Type"))
+        .is_none_or(|typ| typ != "Catalog")
+    {
+        return Err(PDFProcessingError::General(format!(
+            "/Root {} is not a catalog",
+            root
+        )));
+    }
+    return Ok(());
+}
+
+/// A PDF document read from a buffer.
+///
+/// The header, cross-reference sections and trailer are parsed when the document is opened.
+/// Indirect objects are only parsed when first asked for through [`Document::get`], and cached
+/// from then on.
+pub struct Document {
+    buf: Vec<u8>,
+    pdf: RefCell<PDF>,
+    trailer: Dictionary,
+    objects: RefCell<HashMap<Reference, Rc<Object>>>,
+    /// The version declared by the header
+    header_version: Version,
+    repaired: bool,
+}
+
+impl Document {
+    pub fn from_bytes(buf: Vec<u8>) -> Result<Document, PDFProcessingError> {
+        return OpenOptions::new().open_bytes(buf);
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Document, PDFProcessingError> {
+        return OpenOptions::new().open(path);
+    }
+
+    /// Whether the cross-reference table was rebuilt by scanning the file, see
+    /// [`OpenOptions::repair`]
+    pub fn repaired(&self) -> bool {
+        return self.repaired;
+    }
+
+    /// The version the document conforms to: the header's, unless the catalog's `/Version` is a
+    /// later one (7.7.2)
+    pub fn version(&self) -> Version {
+        let catalog = self
+            .catalog()
+            .ok()
+            .and_then(|catalog| Version::parse(catalog.as_dict()?.get_name("Version")?.as_bytes()));
+        return match catalog {
+            Some(version) if version > self.header_version => version,
+            _ => self.header_version,
+        };
+    }
+
+    /// The trailer dictionary (7.5.5) of the newest cross-reference section
+    pub fn trailer(&self) -> &Dictionary {
+        return &self.trailer;
+    }
+
+    /// The object `reference` points to, the null object if it is not defined
+    pub fn get(&self, reference: Reference) -> Result<Rc<Object>, PDFProcessingError> {
+        if let Some(object) = self.objects.borrow().get(&reference) {
+            return Ok(Rc::clone(object));
+        }
+
+        let object = Rc::new(self.pdf.borrow_mut().resolve(&self.buf, reference)?);
+        self.objects
+            .borrow_mut()
+            .insert(reference, Rc::clone(&object));
+        return Ok(object);
+    }
+
+    /// The object `reference` points to, borrowed from the file rather than copied and cached
+    /// like [`Document::get`] does. Objects stored in object streams (7.5.7) only exist decoded,
+    /// so are only available through [`Document::get`].
+    pub fn get_ref(&self, reference: Reference) -> Result<ObjectRef<'_>, PDFProcessingError> {
+        return self.pdf.borrow_mut().resolve_ref(&self.buf, reference);
+    }
+
+    /// The document catalog (7.7.2), referred to by the trailer's `/Root`
+    pub fn catalog(&self) -> Result<Rc<Object>, PDFProcessingError> {
+        let root = self
+            .trailer
+            .get_ref("Root")
+            .ok_or_else(|| PDFProcessingError::General("Trailer is missing /Root".to_string()))?;
+        return self.get(root);
+    }
+
+    /// Recoverable problems found so far, including while resolving objects
+    pub fn warnings(&self) -> Vec<Warning> {
+        return self.pdf.borrow().warnings.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a file with a single cross-reference table holding `objects`, numbered from 1
+    fn build(objects: &[&str]) -> Vec<u8> {
+        let mut buf = b"%PDF-1.7\n".to_vec();
+        let mut offsets = vec![];
+        for (i, body) in objects.iter().enumerate() {
+            offsets.push(buf.len());
+            buf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, body).as_bytes());
+        }
+
+        let xref_offset = buf.len();
+        buf.extend_from_slice(
+            format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+        );
+        for offset in offsets {
+            buf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        buf.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                objects.len() + 1,
+                xref_offset
+            )
+            .as_bytes(),
+        );
+        return buf;
+    }
+
+    /// Replaces the `startxref` section at the end of `buf` with one pointing to `offset`
+    fn with_startxref(mut buf: Vec<u8>, offset: usize) -> Vec<u8> {
+        let position = buf
+            .windows(b"startxref".len())
+            .rposition(|w| w == b"startxref")
+            .expect("Missing startxref");
+        buf.truncate(position);
+        buf.extend_from_slice(format!("startxref\n{}\n%%EOF\n", offset).as_bytes());
+        return buf;
+    }
+
+    #[test]
+    fn resolves_objects_lazily() {
+        let buf = build(&[
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Type /Pages /Kids [] /Count 0 >>",
+        ]);
+        let doc = Document::from_bytes(buf).expect("Failed to open document");
+        assert_eq!(doc.trailer().get_int("Size"), Some(3));
+
+        let catalog = doc.catalog().expect("Failed to resolve catalog");
+        let pages = catalog.as_dict().unwrap().get_ref("Pages").unwrap();
+        assert_eq!(pages, Reference::new(2, 0));
+
+        let first = doc.get(pages).expect("Failed to resolve pages");
+        assert_eq!(first.as_dict().unwrap().get_int("Count"), Some(0));
+        let second = doc.get(pages).expect("Failed to resolve pages");
+        assert!(Rc::ptr_eq(&first, &second));
+
+        assert!(doc.get(Reference::new(7, 0)).unwrap().is_null());
+        assert!(doc.warnings().is_empty());
+    }
+
+    #[test]
+    fn borrows_objects_from_the_file() {
+        let buf = build(&[
+            "<< /Type /Catalog >>",
+            "<< /Length 5 >>\nstream\nhello\nendstream",
+        ]);
+        let doc = Document::from_bytes(buf).expect("Failed to open document");
+
+        let object = doc.get_ref(Reference::new(2, 0)).unwrap();
+        let stream = object.as_stream().unwrap();
+        assert_eq!(stream.data, b"hello");
+        assert_eq!(stream.dict.get_int("Length"), Some(5));
+        assert_eq!(object.to_owned(), *doc.get(Reference::new(2, 0)).unwrap());
+        assert!(doc.get_ref(Reference::new(7, 0)).unwrap().is_null());
+    }
+
+    #[test]
+    fn version_uses_later_catalog_version() {
+        let doc = Document::from_bytes(build(&["<< /Type /Catalog /Version /2.0 >>"])).unwrap();
+        assert_eq!(doc.version(), Version::new(2, 0));
+
+        let doc = Document::from_bytes(build(&["<< /Type /Catalog /Version /1.3 >>"])).unwrap();
+        assert_eq!(doc.version(), Version::new(1, 7));
+        assert_eq!(doc.version().to_string(), "1.7");
+    }
+
+    #[test]
+    fn shifts_offsets_after_junk_prefix() {
+        let mut buf = b"junk before the header\n".to_vec();
+        buf.extend(build(&[
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Count 0 >>",
+        ]));
+
+        let doc = Document::from_bytes(buf.clone()).expect("Failed to open document");
+        let catalog = doc.catalog().expect("Failed to resolve catalog");
+        assert_eq!(
+            catalog.as_dict().unwrap().get_ref("Pages"),
+            Some(Reference::new(2, 0))
+        );
+        let warnings = doc.warnings();
+        assert_eq!(warnings.len(), 2);
+        // Objects can be resolved while the warnings are held
+        for _ in warnings.iter() {
+            assert!(doc.get(Reference::new(2, 0)).is_ok());
+        }
+
+        // Shifting a huge startxref does not overflow
+        let buf = with_startxref(buf, usize::MAX);
+        assert!(matches!(
+            Document::from_bytes(buf),
+            Err(PDFProcessingError::Syntax(usize::MAX, _))
+        ));
+    }
+
+    #[test]
+    fn rejects_startxref_not_at_xref() {
+        let buf = build(&["<< /Type /Catalog >>"]);
+        let trailer = buf.windows(7).position(|w| w == b"trailer").unwrap();
+        // Into an object's header, onto the trailer keyword and past the end of the file
+        for offset in [12, trailer, buf.len() + 100] {
+            assert!(matches!(
+                Document::from_bytes(with_startxref(buf.clone(), offset)),
+                Err(PDFProcessingError::Syntax(at, _)) if at == offset
+            ));
+        }
+    }
+
+    #[test]
+    fn finds_startxref_before_trailing_garbage() {
+        let mut buf = build(&["<< /Type /Catalog >>"]);
+        buf.extend_from_slice(b"\r\n-- \r\nSent through a mail gateway\r\n");
+        buf.resize(buf.len() + 900, b'=');
+        let doc = Document::from_bytes(buf.clone()).expect("Failed to open document");
+        assert!(doc.catalog().is_ok());
+
+        // startxref out of the last 1024 bytes is not searched for
+        buf.resize(buf.len() + 200, b'=');
+        assert!(Document::from_bytes(buf).is_err());
+    }
+
+    #[test]
+    fn rejects_short_buffers() {
+        let buf = b"%PDF-1.0\nstartxref\n9\n%%EOF\n";
+        for len in 0..27 {
+            assert!(Document::from_bytes(buf[..len].to_vec()).is_err());
+        }
+    }
+
+    #[test]
+    fn repairs_broken_xref() {
+        let buf = with_startxref(
+            build(&["<< /Type /Catalog /Pages 2 0 R >>", "<< /Count 3 >>"]),
+            12,
+        );
+
+        assert!(Document::from_bytes(buf.clone()).is_err());
+        let doc = OpenOptions::new()
+            .repair(true)
+            .open_bytes(buf)
+            .expect("Failed to repair document");
+        assert!(doc.repaired());
+        let pages = doc.get(Reference::new(2, 0)).unwrap();
+        assert_eq!(pages.as_dict().unwrap().get_int("Count"), Some(3));
+        assert_eq!(doc.trailer().get_ref("Root"), Some(Reference::new(1, 0)));
+
+        let doc = Document::from_bytes(build(&["<< /Type /Catalog >>"])).unwrap();
+        assert!(!doc.repaired());
+    }
+
+    #[test]
+    fn repairs_shifted_offsets() {
+        // Bytes inserted after the header move every object, but startxref is kept correct
+        let mut buf = build(&["<< /Type /Catalog /Pages 2 0 R >>", "<< /Count 3 >>"]);
+        buf.splice(9..9, b"junk\n".iter().copied());
+        let xref = buf.windows(5).position(|w| w == b"xref\n").unwrap();
+        let buf = with_startxref(buf, xref);
+
+        let doc = Document::from_bytes(buf.clone()).expect("Failed to open document");
+        assert!(!doc.repaired());
+        assert!(doc.catalog().is_err());
+
+        let doc = OpenOptions::new()
+            .repair(true)
+            .open_bytes(buf)
+            .expect("Failed to repair document");
+        assert!(doc.repaired());
+        let catalog = doc.catalog().expect("Failed to resolve catalog");
+        assert_eq!(
+            catalog.as_dict().unwrap().get_ref("Pages"),
+            Some(Reference::new(2, 0))
+        );
+        let pages = doc.get(Reference::new(2, 0)).unwrap();
+        assert_eq!(pages.as_dict().unwrap().get_int("Count"), Some(3));
+    }
+
+    #[test]
+    fn open_reports_io_errors() {
+        let err = Document::open("does/not/exist.pdf").err().unwrap();
+        assert!(matches!(err, PDFProcessingError::IO(_)));
+        assert!(std::error::Error::source(&err).is_some());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::lexer::{self, PDFProcessingError};
+
+fn is_whitespace(byte: &u8) -> bool {
+    return lexer::WhitespaceType::try_from(byte).is_ok();
+}
+
+/// Decodes pairs of hexadecimal digits (7.4.2) up to the `>` end-of-data marker, ignoring
+/// whitespace. A missing final digit is taken to be 0.
+pub(crate) fn ascii_hex_decode(data: &[u8]) -> Result<Vec<u8>, PDFProcessingError> {
+    let mut output = Vec::with_capacity(data.len() / 2);
+    let mut high = None;
+    for (offset, byte) in data.iter().enumerate() {
+        if *byte == b'>' {
+            break;
+        }
+        if is_whitespace(byte) {
+            continue;
+        }
+        let digit = (*byte as char).to_digit(16).ok_or_else(|| {
+            PDFProcessingError::Syntax(
+                offset,
+                format!("Invalid ASCIIHexDecode digit {:?}", *byte as char),
+            )
+        })? as u8;
+        match high.take() {
+            Some(high) => output.push(high << 4 | digit),
+            None => high = Some(digit),
+        }
+    }
+    if let Some(high) = high {
+        output.push(high << 4);
+    }
+    return Ok(output);
+}
+
+pub(crate) fn ascii_hex_encode(data: &[u8]) -> Vec<u8> {
+    const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+    let mut output = Vec::with_capacity(data.len() * 2 + 1);
+    for byte in data {
+        output.push(DIGITS[(byte >> 4) as usize]);
+        output.push(DIGITS[(byte & 0x0F) as usize]);
+    }
+    output.push(b'>');
+    return output;
+}
+
+/// Decodes groups of five base-85 digits into four bytes (7.4.3) up to the `~>` end-of-data
+/// marker, ignoring whitespace. `z` stands for a group of four zero bytes and a final partial
+/// group of n digits decodes to n - 1 bytes.
+pub(crate) fn ascii85_decode(data: &[u8]) -> Result<Vec<u8>, PDFProcessingError> {
+    let data = data.strip_prefix(b"<~").unwrap_or(data);
+    let mut output = Vec::with_capacity(data.len() / 5 * 4);
+    let mut group = [0u8; 5];
+    let mut len = 0;
+    for (offset, byte) in data.iter().enumerate() {
+        match byte {
+            b'~' => break,
+            b'z' if len == 0 => output.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                group[len] = byte - b'!';
+                len += 1;
+                if len == 5 {
+                    output.extend_from_slice(&ascii85_group(&group, offset, false)?);
+                    len = 0;
+                }
+            }
+            _ if is_whitespace(byte) => {}
+            _ => {
+                return Err(PDFProcessingError::Syntax(
+                    offset,
+                    format!("Invalid ASCII85Decode character {:?}", *byte as char),
+                ))
+            }
+        }
+    }
+
+    match len {
+        0 => {}
+        1 => {
+            return Err(PDFProcessingError::General(
+                "ASCII85Decode data ends with a single digit group".to_string(),
+            ))
+        }
+        _ => {
+            // Pad with the highest digit so that the bytes kept round up correctly
+            group[len..].fill(b'u' - b'!');
+            let bytes = ascii85_group(&group, data.len(), true)?;
+            output.extend_from_slice(&bytes[..len - 1]);
+        }
+    }
+    return Ok(output);
+}
+
+/// The four bytes of a group of five digits. A padded final group may exceed the range of four
+/// bytes when its leading bytes are all 0xFF, which the padding then must not carry over.
+fn ascii85_group(
+    group: &[u8; 5],
+    offset: usize,
+    padded: bool,
+) -> Result<[u8; 4], PDFProcessingError> {
+    let value = group
+        .iter()
+        .fold(0u64, |acc, &digit| acc * 85 + digit as u64);
+    let value = match u32::try_from(value) {
+        Ok(value) => value,
+        Err(_) if padded => u32::MAX,
+        Err(_) => {
+            return Err(PDFProcessingError::Syntax(
+                offset,
+                "ASCII85Decode group overflows".to_string(),
+            ))
+        }
+    };
+    return Ok(value.to_be_bytes());
+}
+
+pub(crate) fn ascii85_encode(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len() / 4 * 5 + 7);
+    for chunk in data.chunks(4) {
+        let mut bytes = [0u8; 4];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(bytes);
+        if chunk.len() == 4 && value == 0 {
+            output.push(b'z');
+            continue;
+        }
+
+        let mut digits = [0u8; 5];
+        for digit in digits.iter_mut().rev() {
+            *digit = (value % 85) as u8 + b'!';
+            value /= 85;
+        }
+        output.extend_from_slice(&digits[..chunk.len() + 1]);
+    }
+    output.extend_from_slice(b"~>");
+    return output;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_hex_round_trips() {
+        assert_eq!(ascii_hex_encode(b"\x00\x7F\xAB"), b"007FAB>");
+        assert_eq!(
+            ascii_hex_decode(b"00 7f\nAb>ignored").unwrap(),
+            b"\x00\x7F\xAB"
+        );
+        assert_eq!(ascii_hex_decode(b"4142 4>").unwrap(), b"AB@");
+        assert!(ascii_hex_decode(b"4G>").is_err());
+    }
+
+    #[test]
+    fn ascii85_round_trips() {
+        assert_eq!(ascii85_encode(b"Man "), b"9jqo^~>");
+        assert_eq!(ascii85_encode(b"sure."), b"F*2M7/c~>");
+        assert_eq!(ascii85_encode(&[0; 4]), b"z~>");
+
+        assert_eq!(ascii85_decode(b"9jqo^F*2M7/c~>").unwrap(), b"Man sure.");
+        assert_eq!(ascii85_decode(b"<~9jq\r\no^ z~>").unwrap(), b"Man \0\0\0\0");
+
+        let data: Vec<u8> = (0..=255).collect();
+        for len in 0..8 {
+            let encoded = ascii85_encode(&data[..len]);
+            assert_eq!(ascii85_decode(&encoded).unwrap(), &data[..len]);
+        }
+
+        assert_eq!(ascii85_decode(b"s8W~>").unwrap(), b"\xFF\xFF");
+        assert!(ascii85_decode(b"9jqo^F~>").is_err());
+        assert!(ascii85_decode(b"s8W-\"~>").is_err());
+        assert!(ascii85_decode(b"9jqo{~>").is_err());
+    }
+}
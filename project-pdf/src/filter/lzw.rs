@@ -0,0 +1,161 @@
+use crate::lexer::PDFProcessingError;
+
+const CLEAR_TABLE: usize = 256;
+const EOD: usize = 257;
+const MAX_ENTRIES: usize = 4096;
+
+/// Reads codes of a varying width, most significant bit first
+struct CodeReader<'a> {
+    data: &'a [u8],
+    bit: usize,
+}
+
+impl CodeReader<'_> {
+    fn read(&mut self, width: usize) -> Option<usize> {
+        if self.bit + width > self.data.len() * 8 {
+            return None;
+        }
+        let mut code = 0;
+        for bit in self.bit..self.bit + width {
+            code = code << 1 | ((self.data[bit / 8] >> (7 - bit % 8)) & 1) as usize;
+        }
+        self.bit += width;
+        return Some(code);
+    }
+}
+
+/// Decodes LZW data (7.4.4.2) of 9 to 12 bit codes. With `early_change` the code width grows one
+/// code early, as the `/EarlyChange` default of 1 specifies. Data ending without the EOD code is
+/// decoded as far as it goes, and data decoding to more than `limit` bytes is an error.
+pub(crate) fn lzw_decode(
+    data: &[u8],
+    early_change: bool,
+    limit: usize,
+) -> Result<Vec<u8>, PDFProcessingError> {
+    let initial = || -> Vec<Vec<u8>> {
+        let mut table: Vec<Vec<u8>> = (0..=255u8).map(|byte| vec![byte]).collect();
+        table.extend([vec![], vec![]]); // clear-table and EOD
+        return table;
+    };
+
+    let mut reader = CodeReader { data, bit: 0 };
+    let mut output = Vec::with_capacity(data.len().saturating_mul(2).min(limit));
+    let mut table = initial();
+    let mut width = 9;
+    let mut prev: Option<Vec<u8>> = None;
+    while let Some(code) = reader.read(width) {
+        match code {
+            CLEAR_TABLE => {
+                table = initial();
+                width = 9;
+                prev = None;
+                continue;
+            }
+            EOD => break,
+            _ => {}
+        }
+
+        let entry = match (table.get(code), &prev) {
+            (Some(entry), _) => entry.clone(),
+            // The code being defined by this very step
+            (None, Some(prev)) if code == table.len() => [&prev[..], &prev[..1]].concat(),
+            _ => {
+                return Err(PDFProcessingError::General(format!(
+                    "Invalid LZWDecode code {}",
+                    code
+                )))
+            }
+        };
+        output.extend_from_slice(&entry);
+        if output.len() > limit {
+            return Err(PDFProcessingError::General(format!(
+                "LZWDecode data decodes to more than {} bytes",
+                limit
+            )));
+        }
+
+        if let Some(prev) = prev {
+            if table.len() < MAX_ENTRIES {
+                table.push([&prev[..], &entry[..1]].concat());
+            }
+        }
+        prev = Some(entry);
+
+        width = match table.len() + early_change as usize {
+            ..512 => 9,
+            512..1024 => 10,
+            1024..2048 => 11,
+            _ => 12,
+        };
+    }
+    return Ok(output);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes codes the way an encoder would, growing the width like [`lzw_decode`] expects
+    fn encode(data: &[u8], early_change: bool) -> Vec<u8> {
+        let mut table: Vec<Vec<u8>> = (0..=255u8).map(|byte| vec![byte]).collect();
+        table.extend([vec![], vec![]]);
+        let mut codes = vec![(CLEAR_TABLE, 9)];
+        let mut width = 9;
+        let mut current: Vec<u8> = vec![];
+        for &byte in data {
+            let next = [&current[..], &[byte]].concat();
+            if table.contains(&next) {
+                current = next;
+                continue;
+            }
+            codes.push((table.iter().position(|e| *e == current).unwrap(), width));
+            table.push(next);
+            // The decoder adds each entry one code later
+            width = match table.len() - 1 + early_change as usize {
+                ..512 => 9,
+                512..1024 => 10,
+                _ => 11,
+            };
+            current = vec![byte];
+        }
+        codes.push((table.iter().position(|e| *e == current).unwrap(), width));
+        codes.push((EOD, width));
+
+        let mut bits: Vec<bool> = vec![];
+        for (code, width) in codes {
+            bits.extend((0..width).rev().map(|i| code >> i & 1 == 1));
+        }
+        return bits
+            .chunks(8)
+            .map(|byte| {
+                byte.iter()
+                    .enumerate()
+                    .fold(0u8, |acc, (i, &bit)| acc | (bit as u8) << (7 - i))
+            })
+            .collect();
+    }
+
+    #[test]
+    fn decodes_lzw() {
+        // The example of 7.4.4.2
+        let data = [0x80, 0x0B, 0x60, 0x50, 0x22, 0x0C, 0x0C, 0x85, 0x01];
+        assert_eq!(lzw_decode(&data, true, 10).unwrap(), b"-----A---B");
+
+        // Enough distinct sequences to grow the code width past 9 bits
+        let text: Vec<u8> = (0..2000u32).map(|i| (i * i % 251) as u8).collect();
+        for early_change in [true, false] {
+            let encoded = encode(&text, early_change);
+            assert_eq!(
+                lzw_decode(&encoded, early_change, text.len()).unwrap(),
+                text
+            );
+        }
+        assert_ne!(
+            lzw_decode(&encode(&text, true), false, usize::MAX).ok(),
+            Some(text)
+        );
+
+        assert!(lzw_decode(&[0xFF, 0x80], true, usize::MAX).is_err());
+        assert!(lzw_decode(&data, true, 9).is_err());
+    }
+}
@@ -0,0 +1,456 @@
+use std::borrow::Cow;
+
+use miniz_oxide::inflate::core::{self, inflate_flags, DecompressorOxide};
+use miniz_oxide::inflate::TINFLStatus;
+
+use crate::lexer::PDFProcessingError;
+use crate::object::{Dictionary, Object};
+
+mod ascii;
+mod lzw;
+mod predictor;
+
+/// The most bytes a filter may decode a stream to, so that a few bytes of compressed data cannot
+/// exhaust memory
+pub const MAX_DECODED_LEN: usize = 256 << 20;
+
+/// A standard filter (7.4) that stream data is encoded with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    ASCIIHexDecode,
+    ASCII85Decode,
+    LZWDecode,
+    FlateDecode,
+    RunLengthDecode,
+    CCITTFaxDecode,
+    JBIG2Decode,
+    DCTDecode,
+    JPXDecode,
+}
+
+impl Filter {
+    /// The filter with the given name, including the abbreviations used by inline images
+    pub fn from_name(name: &[u8]) -> Option<Filter> {
+        return match name {
+            b"ASCIIHexDecode" | b"AHx" => Some(Filter::ASCIIHexDecode),
+            b"ASCII85Decode" | b"A85" => Some(Filter::ASCII85Decode),
+            b"LZWDecode" | b"LZW" => Some(Filter::LZWDecode),
+            b"FlateDecode" | b"Fl" => Some(Filter::FlateDecode),
+            b"RunLengthDecode" | b"RL" => Some(Filter::RunLengthDecode),
+            b"CCITTFaxDecode" | b"CCF" => Some(Filter::CCITTFaxDecode),
+            b"JBIG2Decode" => Some(Filter::JBIG2Decode),
+            b"DCTDecode" | b"DCT" => Some(Filter::DCTDecode),
+            b"JPXDecode" => Some(Filter::JPXDecode),
+            _ => None,
+        };
+    }
+
+    /// Whether the filter is an image codec, which [`decode`] leaves the data encoded with
+    pub fn is_image_codec(&self) -> bool {
+        return matches!(
+            self,
+            Filter::CCITTFaxDecode | Filter::JBIG2Decode | Filter::DCTDecode | Filter::JPXDecode
+        );
+    }
+
+    /// The extension of a file that data encoded with the filter can be written to as is. CCITT
+    /// and JBIG2 data lack the headers of a standalone file, so have none.
+    pub fn extension(&self) -> Option<&'static str> {
+        return match self {
+            Filter::DCTDecode => Some("jpg"),
+            Filter::JPXDecode => Some("jp2"),
+            _ => None,
+        };
+    }
+
+    /// Decodes `data` given the filter's `/DecodeParms` entry
+    pub fn decode(
+        &self,
+        data: &[u8],
+        params: Option<&Dictionary>,
+    ) -> Result<Vec<u8>, PDFProcessingError> {
+        return match self {
+            Filter::ASCIIHexDecode => ascii::ascii_hex_decode(data),
+            Filter::ASCII85Decode => ascii::ascii85_decode(data),
+            Filter::LZWDecode => {
+                let early_change = params
+                    .and_then(|params| params.get_int("EarlyChange"))
+                    .unwrap_or(1);
+                let decoded = lzw::lzw_decode(data, early_change != 0, MAX_DECODED_LEN)?;
+                predictor::unpredict(decoded, params)
+            }
+            Filter::FlateDecode => {
+                predictor::unpredict(flate_decode(data, MAX_DECODED_LEN)?, params)
+            }
+            Filter::RunLengthDecode => run_length_decode(data, MAX_DECODED_LEN),
+            _ => Err(PDFProcessingError::General(format!(
+                "Decoding {:?} image data is not supported",
+                self
+            ))),
+        };
+    }
+
+    /// Encodes `data` so that [`Filter::decode`] without parameters restores it
+    pub fn encode(&self, data: &[u8]) -> Result<Vec<u8>, PDFProcessingError> {
+        return match self {
+            Filter::ASCIIHexDecode => Ok(ascii::ascii_hex_encode(data)),
+            Filter::ASCII85Decode => Ok(ascii::ascii85_encode(data)),
+            Filter::FlateDecode => Ok(miniz_oxide::deflate::compress_to_vec_zlib(data, 6)),
+            _ => Err(PDFProcessingError::General(format!(
+                "Encoding with {:?} is not supported",
+                self
+            ))),
+        };
+    }
+}
+
+/// An image codec that stopped the filter pipeline, the data being left encoded with it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageCodec {
+    pub filter: Filter,
+    /// The codec's `/DecodeParms`, such as `/K` and `/Columns` of CCITT data or `/JBIG2Globals`
+    pub params: Option<Dictionary>,
+}
+
+/// The result of running stream data through its filters
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoded<'a> {
+    pub data: Cow<'a, [u8]>,
+    /// The image codec `data` is still encoded with, if decoding stopped at one
+    pub image_codec: Option<ImageCodec>,
+}
+
+/// The entries of an image XObject dictionary (8.9.5) needed to make sense of its data
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageParams {
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub bits_per_component: Option<i64>,
+    /// A name or, for parameterised colour spaces, an array; absent for JPX data that carries its
+    /// own
+    pub color_space: Option<Object>,
+}
+
+impl ImageParams {
+    pub fn from_dict(dict: &Dictionary) -> ImageParams {
+        return ImageParams {
+            width: dict.get_int("Width"),
+            height: dict.get_int("Height"),
+            bits_per_component: dict.get_int("BitsPerComponent"),
+            color_space: dict.get("ColorSpace").cloned(),
+        };
+    }
+}
+
+/// Decodes stream data through the filters named by the stream's `/Filter` entry, a name or an
+/// array of names applied in order. The `/DecodeParms` entry holds the parameters of a single
+/// filter, or an array of parameters or nulls matching an array of filters. Data without filters
+/// is borrowed.
+///
+/// Image codecs are not decoded: the pipeline stops at the first one, returning the data still
+/// encoded with it so that it can be exported or handed to an image decoder.
+pub fn decode<'a>(
+    data: &'a [u8],
+    filter: Option<&Object>,
+    params: Option<&Object>,
+) -> Result<Decoded<'a>, PDFProcessingError> {
+    let names = match filter {
+        None => vec![],
+        Some(filter) => match (filter.as_name(), filter.as_array()) {
+            (Some(name), _) => vec![name],
+            (_, Some(names)) => names
+                .iter()
+                .map(|name| name.as_name())
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| PDFProcessingError::General("Invalid /Filter array".to_string()))?,
+            _ => return Err(PDFProcessingError::General("Invalid /Filter".to_string())),
+        },
+    };
+
+    let params = match params {
+        None => vec![None; names.len()],
+        Some(params) => match (params.as_dict(), params.as_array()) {
+            (Some(dict), _) if names.len() == 1 => vec![Some(dict)],
+            (Some(_), _) => {
+                return Err(PDFProcessingError::General(format!(
+                    "/DecodeParms is a single dictionary for {} filters",
+                    names.len()
+                )))
+            }
+            (_, Some(params)) => {
+                if params.len() != names.len() {
+                    return Err(PDFProcessingError::General(format!(
+                        "/DecodeParms has {} entries for {} filters",
+                        params.len(),
+                        names.len()
+                    )));
+                }
+                params.iter().map(Object::as_dict).collect()
+            }
+            _ if params.is_null() => vec![None; names.len()],
+            _ => {
+                return Err(PDFProcessingError::General(
+                    "Invalid /DecodeParms".to_string(),
+                ))
+            }
+        },
+    };
+
+    let mut data = Cow::Borrowed(data);
+    for (name, params) in names.into_iter().zip(params) {
+        let filter = Filter::from_name(name.as_bytes()).ok_or_else(|| {
+            PDFProcessingError::General(format!("Unsupported stream filter {}", name))
+        })?;
+        if filter.is_image_codec() {
+            let image_codec = ImageCodec {
+                filter,
+                params: params.cloned(),
+            };
+            return Ok(Decoded {
+                data,
+                image_codec: Some(image_codec),
+            });
+        }
+        data = Cow::Owned(filter.decode(&data, params)?);
+    }
+    return Ok(Decoded {
+        data,
+        image_codec: None,
+    });
+}
+
+/// Inflates zlib (RFC 1950) data, or raw deflate (RFC 1951) data when the zlib header is missing.
+///
+/// Truncated data and a missing or wrong checksum are common in real-world files, so whatever
+/// could be inflated is returned rather than an error. Inflating to more than `limit` bytes is.
+fn flate_decode(data: &[u8], limit: usize) -> Result<Vec<u8>, PDFProcessingError> {
+    let zlib_header = match data {
+        [cmf, flg, ..] => cmf & 0x0F == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    };
+    // Claiming more input is coming makes truncated data report what it decoded so far
+    let mut flags = inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF
+        | inflate_flags::TINFL_FLAG_IGNORE_ADLER32
+        | inflate_flags::TINFL_FLAG_HAS_MORE_INPUT;
+    if zlib_header {
+        flags |= inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER;
+    }
+
+    let mut decompressor = Box::<DecompressorOxide>::default();
+    let mut input = data;
+    let mut output = vec![0; data.len().saturating_mul(2).max(64).min(limit)];
+    let mut out_pos = 0;
+    loop {
+        let (status, in_consumed, out_consumed) =
+            core::decompress(&mut decompressor, input, &mut output, out_pos, flags);
+        input = &input[in_consumed..];
+        out_pos += out_consumed;
+        match status {
+            TINFLStatus::Done => break,
+            TINFLStatus::HasMoreOutput if output.len() >= limit => {
+                return Err(exceeds_limit(Filter::FlateDecode, limit))
+            }
+            TINFLStatus::HasMoreOutput => output.resize((output.len() * 2).min(limit), 0),
+            // Data ending early, before its last block or checksum
+            TINFLStatus::NeedsMoreInput => break,
+            _ if out_pos > 0 => break,
+            status => {
+                return Err(PDFProcessingError::General(format!(
+                    "FlateDecode failed: {:?}",
+                    status
+                )))
+            }
+        }
+    }
+
+    output.truncate(out_pos);
+    return Ok(output);
+}
+
+fn exceeds_limit(filter: Filter, limit: usize) -> PDFProcessingError {
+    return PDFProcessingError::General(format!(
+        "{:?} data decodes to more than {} bytes",
+        filter, limit
+    ));
+}
+
+/// Decodes RunLengthDecode data (7.4.5) of runs of literal bytes and repeated bytes up to the EOD
+/// length byte 128. A run cut short is decoded as far as it goes.
+fn run_length_decode(data: &[u8], limit: usize) -> Result<Vec<u8>, PDFProcessingError> {
+    let mut output = Vec::with_capacity(data.len().saturating_mul(2).min(limit));
+    let mut rest = data;
+    while let Some((&length, tail)) = rest.split_first() {
+        match length {
+            0..=127 => {
+                let len = (length as usize + 1).min(tail.len());
+                output.extend_from_slice(&tail[..len]);
+                rest = &tail[len..];
+            }
+            128 => break,
+            _ => {
+                let Some(&byte) = tail.first() else {
+                    break;
+                };
+                output.resize(output.len() + 257 - length as usize, byte);
+                rest = &tail[1..];
+            }
+        }
+        if output.len() > limit {
+            return Err(exceeds_limit(Filter::RunLengthDecode, limit));
+        }
+    }
+    return Ok(output);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniz_oxide::deflate;
+
+    const TEXT: &[u8] = b"BT /F1 12 Tf 72 712 Td (A stream of text, a stream of text) Tj ET";
+
+    #[test]
+    fn flate_decode_tolerates_damage() {
+        let zlib = deflate::compress_to_vec_zlib(TEXT, 6);
+        assert_eq!(Filter::FlateDecode.decode(&zlib, None).unwrap(), TEXT);
+
+        // Missing and wrong checksums
+        let unchecked = &zlib[..zlib.len() - 4];
+        assert_eq!(Filter::FlateDecode.decode(unchecked, None).unwrap(), TEXT);
+        let mut wrong = zlib.clone();
+        *wrong.last_mut().unwrap() ^= 0xFF;
+        assert_eq!(Filter::FlateDecode.decode(&wrong, None).unwrap(), TEXT);
+
+        // Raw deflate data without the zlib header
+        let raw = deflate::compress_to_vec(TEXT, 6);
+        assert_eq!(Filter::FlateDecode.decode(&raw, None).unwrap(), TEXT);
+
+        // Truncated data decodes as far as it goes
+        let truncated = Filter::FlateDecode
+            .decode(&zlib[..zlib.len() / 2], None)
+            .unwrap();
+        assert!(TEXT.starts_with(&truncated));
+
+        assert!(Filter::FlateDecode
+            .decode(b"\xFF\xFF\xFF\xFF", None)
+            .is_err());
+    }
+
+    #[test]
+    fn decoding_is_limited() {
+        let bomb = deflate::compress_to_vec_zlib(&vec![0; 1 << 20], 9);
+        assert!(bomb.len() < 4096);
+        assert_eq!(flate_decode(&bomb, 1 << 20).unwrap().len(), 1 << 20);
+        assert!(flate_decode(&bomb, (1 << 20) - 1).is_err());
+
+        // Limits below the initial buffer size
+        let small = deflate::compress_to_vec_zlib(b"small", 6);
+        assert_eq!(flate_decode(&small, 5).unwrap(), b"small");
+        assert!(flate_decode(&small, 4).is_err());
+        assert!(flate_decode(&small, 0).is_err());
+
+        let runs = [129, b'x'].repeat(10);
+        assert_eq!(run_length_decode(&runs, 1280).unwrap().len(), 1280);
+        assert!(run_length_decode(&runs, 1000).is_err());
+    }
+
+    #[test]
+    fn decode_applies_filters_in_order() {
+        let once = deflate::compress_to_vec_zlib(TEXT, 6);
+        let twice = deflate::compress_to_vec_zlib(&once, 6);
+
+        assert!(matches!(
+            decode(TEXT, None, None).map(|d| d.data),
+            Ok(Cow::Borrowed(TEXT))
+        ));
+        let filter = Object::name("FlateDecode");
+        assert_eq!(decode(&once, Some(&filter), None).unwrap().data, TEXT);
+        let filters = Object::array(vec![Object::name("Fl"), Object::name("FlateDecode")]);
+        assert_eq!(decode(&twice, Some(&filters), None).unwrap().data, TEXT);
+
+        // ASCII85 over Flate over a PNG predictor, with parameters for the second filter only
+        let predicted = [2, 1, 2, 3, 2, 1, 1, 1];
+        let flate = Filter::FlateDecode.encode(&predicted).unwrap();
+        let encoded = Filter::ASCII85Decode.encode(&flate).unwrap();
+        let filters = Object::array(vec![
+            Object::name("ASCII85Decode"),
+            Object::name("FlateDecode"),
+        ]);
+        let params = Object::array(vec![
+            Object::null(),
+            Object::dictionary(Dictionary::new().with("Predictor", 12).with("Columns", 3)),
+        ]);
+        assert_eq!(
+            decode(&encoded, Some(&filters), Some(&params))
+                .unwrap()
+                .data,
+            &[1, 2, 3, 2, 3, 4][..]
+        );
+        let short = Object::array(vec![Object::null()]);
+        assert!(decode(&encoded, Some(&filters), Some(&short)).is_err());
+        let lone = Object::dictionary(Dictionary::new().with("Predictor", 12).with("Columns", 3));
+        assert!(decode(&encoded, Some(&filters), Some(&lone)).is_err());
+        assert_eq!(
+            decode(&flate, Some(&Object::name("Fl")), Some(&lone))
+                .unwrap()
+                .data,
+            &[1, 2, 3, 2, 3, 4][..]
+        );
+
+        let hex = Filter::ASCIIHexDecode.encode(TEXT).unwrap();
+        assert_eq!(
+            decode(&hex, Some(&Object::name("AHx")), None).unwrap().data,
+            TEXT
+        );
+
+        let unsupported = Object::name("Crypt");
+        assert!(decode(TEXT, Some(&unsupported), None).is_err());
+    }
+
+    #[test]
+    fn stops_at_image_codecs() {
+        let jpeg = b"\xFF\xD8\xFF\xE0\x00\x10JFIF\x00";
+        let encoded = Filter::ASCIIHexDecode.encode(jpeg).unwrap();
+        let filters = Object::array(vec![Object::name("AHx"), Object::name("DCTDecode")]);
+        let params = Object::array(vec![
+            Object::null(),
+            Object::dictionary(Dictionary::new().with("ColorTransform", 0)),
+        ]);
+
+        let decoded = decode(&encoded, Some(&filters), Some(&params)).unwrap();
+        assert_eq!(decoded.data, &jpeg[..]);
+        let codec = decoded.image_codec.unwrap();
+        assert_eq!(codec.filter, Filter::DCTDecode);
+        assert_eq!(codec.filter.extension(), Some("jpg"));
+        assert_eq!(codec.params.unwrap().get_int("ColorTransform"), Some(0));
+
+        let decoded = decode(b"\x00\x01", Some(&Object::name("CCF")), None).unwrap();
+        assert!(matches!(decoded.data, Cow::Borrowed(b"\x00\x01")));
+        assert_eq!(decoded.image_codec.unwrap().filter.extension(), None);
+        assert!(Filter::JPXDecode.decode(b"", None).is_err());
+
+        let dict = Dictionary::new()
+            .with("Subtype", Object::name("Image"))
+            .with("Width", 640)
+            .with("Height", 480)
+            .with("ColorSpace", Object::name("DeviceRGB"))
+            .with("BitsPerComponent", 8);
+        let params = ImageParams::from_dict(&dict);
+        assert_eq!((params.width, params.height), (Some(640), Some(480)));
+        assert_eq!(params.bits_per_component, Some(8));
+        assert_eq!(params.color_space, Some(Object::name("DeviceRGB")));
+    }
+
+    #[test]
+    fn decodes_run_length() {
+        let data = [2, b'a', b'b', b'c', 253, b'x', 0, b'y', 128, 5];
+        assert_eq!(
+            run_length_decode(&data, MAX_DECODED_LEN).unwrap(),
+            b"abcxxxxy"
+        );
+        assert_eq!(
+            run_length_decode(&[4, b'a', b'b'], MAX_DECODED_LEN).unwrap(),
+            b"ab"
+        );
+    }
+}
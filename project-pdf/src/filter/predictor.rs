@@ -0,0 +1,243 @@
+use crate::lexer::PDFProcessingError;
+use crate::object::Dictionary;
+
+/// The `/DecodeParms` entries describing how samples were predicted before compression
+/// (7.4.4.4, Table 8)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Predictor {
+    predictor: i64,
+    colors: usize,
+    bits_per_component: usize,
+    columns: usize,
+}
+
+impl Predictor {
+    fn from_params(params: &Dictionary) -> Result<Predictor, PDFProcessingError> {
+        let get = |key: &str, default: i64, valid: &dyn Fn(usize) -> bool| {
+            let value = params.get_int(key).unwrap_or(default);
+            return usize::try_from(value)
+                .ok()
+                .filter(|&v| valid(v))
+                .ok_or_else(|| PDFProcessingError::General(format!("Invalid /{} {}", key, value)));
+        };
+
+        return Ok(Predictor {
+            predictor: params.get_int("Predictor").unwrap_or(1),
+            colors: get("Colors", 1, &|v| (1..=32).contains(&v))?,
+            bits_per_component: get("BitsPerComponent", 8, &|v| [1, 2, 4, 8, 16].contains(&v))?,
+            columns: get("Columns", 1, &|v| v >= 1)?,
+        });
+    }
+
+    /// Bytes per complete pixel, at least 1
+    fn pixel_len(&self) -> usize {
+        return (self.colors * self.bits_per_component).div_ceil(8);
+    }
+
+    /// Bytes per row of samples, excluding the PNG filter type byte
+    fn row_len(&self) -> Result<usize, PDFProcessingError> {
+        return (self.colors * self.bits_per_component)
+            .checked_mul(self.columns)
+            .map(|bits| bits.div_ceil(8))
+            .ok_or_else(|| {
+                PDFProcessingError::General(format!("/Columns {} overflows", self.columns))
+            });
+    }
+}
+
+/// Reverses the predictor named by `params` on data that has been decompressed, for the filters
+/// that accept a predictor. Predictors 10 to 15 are PNG predictors, chosen per row, and 2 is the
+/// TIFF predictor. A last row cut short is decoded as far as it goes.
+pub(crate) fn unpredict(
+    data: Vec<u8>,
+    params: Option<&Dictionary>,
+) -> Result<Vec<u8>, PDFProcessingError> {
+    let predictor = match params {
+        Some(params) => Predictor::from_params(params)?,
+        None => return Ok(data),
+    };
+    if predictor.predictor == 1 {
+        return Ok(data);
+    }
+    // Rows longer than the data would only size buffers by untrusted parameters
+    let row_len = predictor.row_len()?;
+    if row_len > data.len() && !data.is_empty() {
+        return Err(PDFProcessingError::General(format!(
+            "Predictor rows of {} bytes exceed the {} bytes of data",
+            row_len,
+            data.len()
+        )));
+    }
+
+    return match predictor.predictor {
+        1 => Ok(data),
+        2 => Ok(unpredict_tiff(data, &predictor, row_len)),
+        10..=15 => unpredict_png(&data, &predictor, row_len),
+        other => Err(PDFProcessingError::General(format!(
+            "Unsupported /Predictor {}",
+            other
+        ))),
+    };
+}
+
+fn unpredict_png(
+    data: &[u8],
+    predictor: &Predictor,
+    row_len: usize,
+) -> Result<Vec<u8>, PDFProcessingError> {
+    let bpp = predictor.pixel_len();
+
+    let mut output = Vec::with_capacity(data.len());
+    let mut prev = vec![0u8; row_len];
+    for encoded in data.chunks(row_len + 1) {
+        let (&typ, encoded) = match encoded.split_first() {
+            Some(split) => split,
+            None => break,
+        };
+
+        let mut row = encoded.to_vec();
+        for i in 0..row.len() {
+            let left = if i >= bpp { row[i - bpp] } else { 0 };
+            let up = prev[i];
+            let up_left = if i >= bpp { prev[i - bpp] } else { 0 };
+            let prediction = match typ {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => {
+                    return Err(PDFProcessingError::General(format!(
+                        "Invalid PNG filter type {}",
+                        typ
+                    )))
+                }
+            };
+            row[i] = row[i].wrapping_add(prediction);
+        }
+
+        output.extend_from_slice(&row);
+        prev[..row.len()].copy_from_slice(&row);
+    }
+    return Ok(output);
+}
+
+/// The PNG Paeth predictor, picking whichever neighbour is closest to `left + up - up_left`
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let p = left as i16 + up as i16 - up_left as i16;
+    let pa = (p - left as i16).abs();
+    let pb = (p - up as i16).abs();
+    let pc = (p - up_left as i16).abs();
+    if pa <= pb && pa <= pc {
+        return left;
+    }
+    if pb <= pc {
+        return up;
+    }
+    return up_left;
+}
+
+/// Each component is predicted by the same component of the pixel to its left, within a row
+fn unpredict_tiff(mut data: Vec<u8>, predictor: &Predictor, row_len: usize) -> Vec<u8> {
+    let bits = predictor.bits_per_component;
+    let components = predictor.colors * predictor.columns;
+    let mask = (1u32 << bits) - 1;
+
+    for row in data.chunks_mut(row_len) {
+        let available = row.len() * 8 / bits;
+        for i in predictor.colors..components.min(available) {
+            let left = read_bits(row, (i - predictor.colors) * bits, bits);
+            let value = read_bits(row, i * bits, bits);
+            write_bits(row, i * bits, bits, value.wrapping_add(left) & mask);
+        }
+    }
+    return data;
+}
+
+/// Reads the `len` bit value starting at bit `offset`, most significant bit first
+fn read_bits(row: &[u8], offset: usize, len: usize) -> u32 {
+    let mut value = 0;
+    for bit in offset..offset + len {
+        value = value << 1 | ((row[bit / 8] >> (7 - bit % 8)) & 1) as u32;
+    }
+    return value;
+}
+
+fn write_bits(row: &mut [u8], offset: usize, len: usize, value: u32) {
+    for (i, bit) in (offset..offset + len).enumerate() {
+        let set = (value >> (len - 1 - i)) & 1 == 1;
+        let mask = 1 << (7 - bit % 8);
+        match set {
+            true => row[bit / 8] |= mask,
+            false => row[bit / 8] &= !mask,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(predictor: i64, colors: i64, bits: i64, columns: i64) -> Dictionary {
+        return Dictionary::new()
+            .with("Predictor", predictor)
+            .with("Colors", colors)
+            .with("BitsPerComponent", bits)
+            .with("Columns", columns);
+    }
+
+    #[test]
+    fn reverses_png_predictors() {
+        #[rustfmt::skip]
+        let data = vec![
+            2, 1, 2, 3,  // Up
+            2, 1, 1, 1,  // Up
+            1, 5, 1, 1,  // Sub
+            4, 1, 2, 1,  // Paeth
+            3, 7, 1, 1,  // Average
+            0, 9, 9,     // None, cut short
+        ];
+        let decoded = unpredict(data, Some(&params(12, 1, 8, 3))).unwrap();
+        assert_eq!(
+            decoded,
+            [1, 2, 3, 2, 3, 4, 5, 6, 7, 6, 8, 9, 10, 10, 10, 9, 9]
+        );
+
+        // The xref stream layout of /W [1 2 1] with /Predictor 12 /Columns 4
+        let data = vec![2, 1, 0, 0x10, 0, 2, 0, 0, 0x20, 0];
+        let decoded = unpredict(
+            data,
+            Some(&Dictionary::new().with("Predictor", 12).with("Columns", 4)),
+        )
+        .unwrap();
+        assert_eq!(decoded, [1, 0, 0x10, 0, 1, 0, 0x30, 0]);
+
+        assert!(unpredict(vec![5, 0, 0, 0], Some(&params(15, 1, 8, 3))).is_err());
+    }
+
+    #[test]
+    fn rejects_hostile_params() {
+        let data = vec![0; 16];
+        assert!(unpredict(data.clone(), Some(&params(12, 1, 8, 2147483647))).is_err());
+        assert!(unpredict(data.clone(), Some(&params(12, 32, 16, i64::MAX / 2))).is_err());
+        assert!(unpredict(data.clone(), Some(&params(2, 1, 8, 17))).is_err());
+        assert!(unpredict(data.clone(), Some(&params(12, 33, 8, 1))).is_err());
+        assert!(unpredict(data.clone(), Some(&params(12, 1, 3, 1))).is_err());
+        assert!(unpredict(data, Some(&params(12, 1, 8, 0))).is_err());
+    }
+
+    #[test]
+    fn reverses_tiff_predictor() {
+        let data = vec![10, 20, 30, 1, 2, 3, 5, 5, 5, 250, 0, 1];
+        let decoded = unpredict(data, Some(&params(2, 3, 8, 2))).unwrap();
+        assert_eq!(decoded, [10, 20, 30, 11, 22, 33, 5, 5, 5, 255, 5, 6]);
+
+        // Four bit components, two rows of four
+        let decoded = unpredict(vec![0x12, 0x11, 0xF1, 0x00], Some(&params(2, 1, 4, 4))).unwrap();
+        assert_eq!(decoded, [0x13, 0x45, 0xF0, 0x00]);
+
+        // Sixteen bit components
+        let decoded = unpredict(vec![0x01, 0x00, 0x00, 0xFF], Some(&params(2, 1, 16, 2))).unwrap();
+        assert_eq!(decoded, [0x01, 0x00, 0x01, 0xFF]);
+    }
+}
@@ -1,4 +1,5 @@
-use std::fmt::{self, Display};
+use std::borrow::Cow;
+use std::fmt;
 // type PDFProcessingError= ();
 type Result<T> = std::result::Result<T, PDFProcessingError>;
 
@@ -9,24 +10,38 @@ pub fn add(left: usize, right: usize) -> usize {
 #[derive(Debug)]
 pub enum PDFProcessingError {
     EOF(String),
+    /// Malformed input at the given byte offset
+    Syntax(usize, String),
     General(String), // TODO: Remove this and replace with more concrete. For now, general error
+    IO(std::io::Error),
 }
 
 impl fmt::Display for PDFProcessingError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             PDFProcessingError::EOF(ref err) => write!(f, "Reached EOF: {}", err),
+            PDFProcessingError::Syntax(offset, ref err) => {
+                write!(f, "Syntax error at offset {}: {}", offset, err)
+            }
             PDFProcessingError::General(ref err) => write!(f, "PDFProcessingError: {}", err),
+            PDFProcessingError::IO(ref err) => write!(f, "I/O error: {}", err),
         }
     }
 }
 
 impl std::error::Error for PDFProcessingError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match *self {
-            // Error cases involving std::error go here
+        // Error cases involving std::error go here
+        return match *self {
+            PDFProcessingError::IO(ref err) => Some(err),
             _ => None,
-        }
+        };
+    }
+}
+
+impl From<std::io::Error> for PDFProcessingError {
+    fn from(err: std::io::Error) -> Self {
+        return PDFProcessingError::IO(err);
     }
 }
 
@@ -158,7 +173,7 @@ impl TryFrom<&[u8]> for KeywordType {
             b"null" => Ok(KeywordType::Null),
             b"stream" => Ok(KeywordType::Stream),
             b"endstream" => Ok(KeywordType::Endstream),
-            b"r" => Ok(KeywordType::R),
+            b"R" => Ok(KeywordType::R),
             b"xref" => Ok(KeywordType::Xref),
             b"trailer" => Ok(KeywordType::Trailer),
             b"n" => Ok(KeywordType::N),
@@ -174,6 +189,16 @@ pub enum TokenType {
     Delimiter(DelimiterType),
     Whitespace(WhitespaceType),
     Keyword(KeywordType),
+    /// A complete literal string, parentheses included: `(value)`
+    LiteralString,
+    /// A complete hexadecimal string, angle brackets included: `<4E6F>`
+    HexString,
+    /// A complete name object, solidus included: `/Name`
+    Name,
+    /// `<<`
+    DictionaryStart,
+    /// `>>`
+    DictionaryEnd,
     Regular,
 }
 
@@ -215,10 +240,28 @@ impl<'a> Lexer {
     }
 
     /// Get the position of the lexer
-    pub fn getp(&mut self) -> usize {
+    pub fn getp(&self) -> usize {
         return self.p;
     }
 
+    /// Moves pointer p past any whitespace and comments
+    pub fn skip_whitespace(&mut self, buf: &'a [u8]) {
+        while let Ok(tok) = self.peek(buf) {
+            match tok.typ {
+                TokenType::Whitespace(_) => self.p = tok.offset + tok.lexeme.len(),
+                TokenType::Delimiter(DelimiterType::PercentSign) => {
+                    // 7.2.4 A comment runs until the end of the line
+                    let mut end = tok.offset + 1;
+                    while end < buf.len() && buf[end] != b'\r' && buf[end] != b'\n' {
+                        end += 1;
+                    }
+                    self.p = end;
+                }
+                _ => break,
+            }
+        }
+    }
+
     /// Grabs the next token and moves pointer p forward
     pub fn next(&mut self, buf: &'a [u8]) -> Result<Token<'a>> {
         let tok = self.peek(buf)?;
@@ -281,6 +324,53 @@ impl<'a> Lexer {
             Err(_) => (), // nop
         };
 
+        // Literal strings may contain any delimiter, so they are lexed as a single token
+        if buf[start] == DelimiterType::LeftParen as u8 {
+            let end = scan_literal_string(buf, start)?;
+            return Ok(Token {
+                lexeme: &buf[start..end],
+                offset: start,
+                typ: TokenType::LiteralString,
+            });
+        }
+
+        // A single '<' opens a hexadecimal string, a double one a dictionary
+        if buf[start] == DelimiterType::LeftAngleBrack as u8 {
+            if buf.get(start + 1) == Some(&(DelimiterType::LeftAngleBrack as u8)) {
+                return Ok(Token {
+                    lexeme: &buf[start..start + 2],
+                    offset: start,
+                    typ: TokenType::DictionaryStart,
+                });
+            }
+            let end = scan_hex_string(buf, start)?;
+            return Ok(Token {
+                lexeme: &buf[start..end],
+                offset: start,
+                typ: TokenType::HexString,
+            });
+        }
+
+        if buf[start] == DelimiterType::RightAngleBrack as u8
+            && buf.get(start + 1) == Some(&(DelimiterType::RightAngleBrack as u8))
+        {
+            return Ok(Token {
+                lexeme: &buf[start..start + 2],
+                offset: start,
+                typ: TokenType::DictionaryEnd,
+            });
+        }
+
+        // 7.3.5 A name is the solidus followed by regular characters only
+        if buf[start] == DelimiterType::Solidus as u8 {
+            let end = scan_regular(buf, start + 1);
+            return Ok(Token {
+                lexeme: &buf[start..end],
+                offset: start,
+                typ: TokenType::Name,
+            });
+        }
+
         // Check for delimiter
         match DelimiterType::try_from(&buf[start]) {
             Ok(delim) => {
@@ -294,14 +384,7 @@ impl<'a> Lexer {
         };
 
         // Increment end until EOF, Delimiter, or Whitespace.
-        let mut end = start + 1;
-
-        while end < buf.len() {
-            if WhitespaceType::is(&buf[end]) || DelimiterType::is(&buf[end]) {
-                break;
-            }
-            end += 1;
-        }
+        let end = scan_regular(buf, start + 1);
 
         // If keyword
         if let Ok(kw) = KeywordType::try_from(&buf[start..end]) {
@@ -320,6 +403,199 @@ impl<'a> Lexer {
     }
 }
 
+/// Increments `start` until EOF, Delimiter, or Whitespace.
+fn scan_regular(buf: &[u8], start: usize) -> usize {
+    let mut end = start;
+    while end < buf.len() {
+        if WhitespaceType::is(&buf[end]) || DelimiterType::is(&buf[end]) {
+            break;
+        }
+        end += 1;
+    }
+    return end;
+}
+
+/// Finds the end of the literal string starting at `start`, returning the offset just past the
+/// closing parenthesis.
+///
+/// 7.3.4.2 Balanced pairs of parentheses within a string need no escaping, an unbalanced
+/// parenthesis must be preceded by a REVERSE SOLIDUS.
+fn scan_literal_string(buf: &[u8], start: usize) -> Result<usize> {
+    let mut depth = 0;
+    let mut i = start;
+    while i < buf.len() {
+        match buf[i] {
+            b'\\' => i += 1, // Whatever follows the escape cannot open or close the string
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i + 1);
+                }
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    return Err(PDFProcessingError::EOF(format!(
+        "Unterminated literal string starting at offset {}",
+        start
+    )));
+}
+
+/// Finds the end of the hexadecimal string starting at `start`, returning the offset just past
+/// the closing angle bracket.
+///
+/// 7.3.4.3 Only hexadecimal digits and white-space may appear between the angle brackets.
+fn scan_hex_string(buf: &[u8], start: usize) -> Result<usize> {
+    for (i, byte) in buf.iter().enumerate().skip(start + 1) {
+        if *byte == DelimiterType::RightAngleBrack as u8 {
+            return Ok(i + 1);
+        }
+        if !byte.is_ascii_hexdigit() && !WhitespaceType::is(byte) {
+            return Err(PDFProcessingError::Syntax(
+                i,
+                format!(
+                    "Invalid hexadecimal digit 0x{:02X} in hexadecimal string",
+                    byte
+                ),
+            ));
+        }
+    }
+    return Err(PDFProcessingError::EOF(format!(
+        "Unterminated hexadecimal string starting at offset {}",
+        start
+    )));
+}
+
+/// The value of a single hexadecimal digit
+fn hex_value(byte: u8) -> Option<u8> {
+    return match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    };
+}
+
+/// Decodes the lexeme of a hexadecimal string token, angle brackets included, into the bytes it
+/// represents.
+///
+/// White-space between digits is ignored and a final odd digit is completed with a trailing 0, so
+/// `<901FA>` decodes to the same bytes as `<901FA0>`.
+pub fn decode_hex_string(lexeme: &[u8]) -> Vec<u8> {
+    let inner = match lexeme {
+        [b'<', inner @ .., b'>'] => inner,
+        _ => lexeme,
+    };
+
+    let mut out = Vec::with_capacity(inner.len() / 2 + 1);
+    let mut high: Option<u8> = None;
+    for &byte in inner {
+        let Some(nibble) = hex_value(byte) else {
+            continue; // White-space, invalid digits are rejected by the lexer
+        };
+        match high.take() {
+            Some(h) => out.push(h << 4 | nibble),
+            None => high = Some(nibble),
+        }
+    }
+    if let Some(h) = high {
+        out.push(h << 4);
+    }
+    return out;
+}
+
+/// Decodes the lexeme of a name token, solidus included, into the bytes of the name.
+///
+/// 7.3.5 Any character may be written as `#` followed by its two digit hexadecimal code. A `#`
+/// that is not followed by two hexadecimal digits is kept as is, as older writers did not escape
+/// it. Names without escapes are borrowed from the lexeme.
+pub fn decode_name(lexeme: &[u8]) -> Cow<'_, [u8]> {
+    let name = lexeme.strip_prefix(b"/").unwrap_or(lexeme);
+    if !name.contains(&b'#') {
+        return Cow::Borrowed(name);
+    }
+
+    let mut out = Vec::with_capacity(name.len());
+    let mut i = 0;
+    while i < name.len() {
+        if let (b'#', Some(&[high, low])) = (name[i], name.get(i + 1..i + 3)) {
+            if let (Some(high), Some(low)) = (hex_value(high), hex_value(low)) {
+                out.push(high << 4 | low);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(name[i]);
+        i += 1;
+    }
+    return Cow::Owned(out);
+}
+
+/// Decodes the lexeme of a literal string token, parentheses included, into the bytes it
+/// represents.
+///
+/// Escape sequences are replaced by the byte they denote (7.3.4.2, Table 3), a REVERSE SOLIDUS
+/// followed by an end-of-line marker continues the string on the next line, and every unescaped
+/// end-of-line marker (CR, LF or CRLF) is normalized to a single LF.
+pub fn decode_literal_string(lexeme: &[u8]) -> Vec<u8> {
+    let inner = match lexeme {
+        [b'(', inner @ .., b')'] => inner,
+        _ => lexeme,
+    };
+
+    let mut out = Vec::with_capacity(inner.len());
+    let mut i = 0;
+    while i < inner.len() {
+        let byte = inner[i];
+        i += 1;
+        match byte {
+            b'\\' => {
+                let Some(&escaped) = inner.get(i) else {
+                    break; // A trailing REVERSE SOLIDUS is ignored
+                };
+                i += 1;
+                match escaped {
+                    b'n' => out.push(b'\n'),
+                    b'r' => out.push(b'\r'),
+                    b't' => out.push(b'\t'),
+                    b'b' => out.push(0x08),
+                    b'f' => out.push(0x0C),
+                    b'0'..=b'7' => {
+                        // Up to three octal digits, high-order overflow is ignored
+                        let mut value = escaped - b'0';
+                        let mut digits = 1;
+                        while digits < 3 && i < inner.len() && matches!(inner[i], b'0'..=b'7') {
+                            value = value.wrapping_mul(8).wrapping_add(inner[i] - b'0');
+                            digits += 1;
+                            i += 1;
+                        }
+                        out.push(value);
+                    }
+                    b'\r' => {
+                        // Line continuation, CRLF counts as a single end-of-line marker
+                        if inner.get(i) == Some(&b'\n') {
+                            i += 1;
+                        }
+                    }
+                    b'\n' => (), // Line continuation
+                    // Covers \(, \) and \\. Otherwise the REVERSE SOLIDUS is ignored
+                    other => out.push(other),
+                }
+            }
+            b'\r' => {
+                if inner.get(i) == Some(&b'\n') {
+                    i += 1;
+                }
+                out.push(b'\n');
+            }
+            other => out.push(other),
+        }
+    }
+    return out;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -330,6 +606,130 @@ mod tests {
         assert_eq!(result, 4);
     }
 
+    #[test]
+    fn lexes_literal_string_as_single_token() {
+        let data = b"(a (nested) b \\) c) /Next";
+        let mut lexer = Lexer::new();
+
+        let tok = lexer.next(data).expect("Failed to lex literal string");
+        assert_eq!(tok.typ, TokenType::LiteralString);
+        assert_eq!(tok.lexeme, b"(a (nested) b \\) c)");
+
+        let tok = lexer.next(data).expect("Failed to lex token after string");
+        assert_eq!(tok.typ, TokenType::Name);
+    }
+
+    #[test]
+    fn unterminated_literal_string_is_an_error() {
+        let lexer = Lexer::new();
+        assert!(matches!(
+            lexer.peek(b"(never (closed)"),
+            Err(PDFProcessingError::EOF(_))
+        ));
+    }
+
+    #[test]
+    fn decodes_literal_string_escapes() {
+        assert_eq!(
+            decode_literal_string(b"(a\\n\\(b\\)\\\\\\101\\0053\\q)"),
+            b"a\n(b)\\A\x053q"
+        );
+        // Line continuations and end-of-line normalization
+        assert_eq!(
+            decode_literal_string(b"(one\\\r\ntwo\rthree\r\nfour\\\nfive)"),
+            b"onetwo\nthree\nfourfive"
+        );
+    }
+
+    #[test]
+    fn lexes_hex_strings_and_dictionary_brackets() {
+        let data = b"<</ID <4E 6f\n70>>>";
+        let mut lexer = Lexer::new();
+
+        assert_eq!(lexer.next(data).unwrap().typ, TokenType::DictionaryStart);
+        assert_eq!(lexer.next(data).unwrap().typ, TokenType::Name);
+
+        let tok = lexer.next(data).expect("Failed to lex hex string");
+        assert_eq!(tok.typ, TokenType::HexString);
+        assert_eq!(tok.lexeme, b"<4E 6f\n70>");
+        assert_eq!(decode_hex_string(tok.lexeme), b"Nop");
+
+        assert_eq!(lexer.next(data).unwrap().typ, TokenType::DictionaryEnd);
+    }
+
+    #[test]
+    fn decodes_odd_length_hex_string() {
+        assert_eq!(decode_hex_string(b"<901FA>"), vec![0x90, 0x1F, 0xA0]);
+        assert_eq!(decode_hex_string(b"<>"), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn invalid_hex_digit_reports_offset() {
+        let lexer = Lexer::new();
+        match lexer.peek(b"<4E6G>") {
+            Err(PDFProcessingError::Syntax(offset, _)) => assert_eq!(offset, 4),
+            other => panic!("Expected a syntax error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lexes_names_as_single_token() {
+        let data = b"/Type/Page /A#20B[/]";
+        let mut lexer = Lexer::new();
+
+        let lexemes: Vec<&[u8]> = std::iter::from_fn(|| lexer.next(data).ok())
+            .filter(|tok| tok.typ == TokenType::Name)
+            .map(|tok| tok.lexeme)
+            .collect();
+        assert_eq!(lexemes, vec![&b"/Type"[..], b"/Page", b"/A#20B", b"/"]);
+    }
+
+    #[test]
+    fn decodes_name_escapes() {
+        assert!(matches!(decode_name(b"/Type"), Cow::Borrowed(b"Type")));
+        assert_eq!(decode_name(b"/A#20B#2f"), &b"A B/"[..]);
+        assert_eq!(decode_name(b"/Bad#G1#4"), &b"Bad#G1#4"[..]);
+        assert_eq!(decode_name(b"/"), &b""[..]);
+    }
+
+    #[test]
+    fn lexes_reference_keyword() {
+        let data = b"12 0 R r";
+        let mut lexer = Lexer::new();
+        let types: Vec<TokenType> = std::iter::from_fn(|| lexer.next(data).ok())
+            .map(|tok| tok.typ)
+            .filter(|typ| !matches!(typ, TokenType::Whitespace(_)))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Regular,
+                TokenType::Regular,
+                TokenType::Keyword(KeywordType::R),
+                TokenType::Regular
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_whitespace_and_comments() {
+        let data = b" \r\n% a comment ) with (delimiters\n\t%another\r[";
+        let mut lexer = Lexer::new();
+        lexer.skip_whitespace(data);
+        assert_eq!(
+            lexer.next(data).unwrap().typ,
+            TokenType::Delimiter(DelimiterType::LeftSquareBrack)
+        );
+
+        // Trailing whitespace runs into EOF
+        let data = b"[  ";
+        let mut lexer = Lexer::new();
+        lexer.setp(1);
+        lexer.skip_whitespace(data);
+        assert_eq!(lexer.getp(), data.len());
+        assert!(matches!(lexer.peek(data), Err(PDFProcessingError::EOF(_))));
+    }
+
     //     #[test]
     //     fn creates_tokens() {
     //         let data = b"%PDF-1.7
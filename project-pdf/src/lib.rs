@@ -1,121 +1,407 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
 use lexer::{KeywordType, PDFProcessingError};
+mod document;
+pub mod filter;
 pub mod lexer;
 pub mod object;
+pub mod xref;
+
+pub use document::{Document, OpenOptions, Version};
+
+/// A recoverable problem found while reading a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Warning at offset {}: {}", self.offset, self.message)
+    }
+}
+
+/// The decoded data of an object stream (7.5.7) and the location of the objects it contains
+#[derive(Debug)]
+struct ObjectStream {
+    data: Vec<u8>,
+    /// Object number and offset in `data` of each object, in order
+    objects: Vec<(u32, usize)>,
+}
+
+/// How deep arrays and dictionaries may nest before parsing gives up, rather than overflowing the
+/// stack on hostile files
+const MAX_NESTING: usize = 256;
 
+#[allow(clippy::upper_case_acronyms)]
 struct PDF {
     lexer: lexer::Lexer,
+    warnings: Vec<Warning>,
+    xref: xref::XrefTable,
+    /// Indirect objects being resolved, to break reference cycles
+    resolving: Vec<object::Reference>,
+    /// Decoded object streams by object number
+    object_streams: HashMap<u32, Rc<ObjectStream>>,
+    /// Added to the byte offsets found in the file, which are relative to the header when junk
+    /// precedes it
+    offset_shift: usize,
+    /// Arrays and dictionaries being parsed, see [`MAX_NESTING`]
+    nesting: usize,
 }
 
 #[allow(dead_code)]
 impl PDF {
-    fn header_offset<'a>(&mut self, buf: &'a [u8]) -> Result<usize, PDFProcessingError> {
-        // let HPREFIX = b"%PDF-";
-        // let offset = buf.windows(HPREFIX.len()).position(|w| w == HPREFIX)?
-        return Ok(3);
+    fn new() -> PDF {
+        return PDF {
+            lexer: lexer::Lexer::new(),
+            warnings: vec![],
+            xref: xref::XrefTable::new(),
+            resolving: vec![],
+            object_streams: HashMap::new(),
+            offset_shift: 0,
+            nesting: 0,
+        };
+    }
+
+    fn warn(&mut self, offset: usize, message: String) {
+        self.warnings.push(Warning { offset, message });
+    }
+
+    /// Applies [`PDF::offset_shift`] to an offset found in the file, which may be anything
+    fn shift(&self, offset: usize) -> Result<usize, PDFProcessingError> {
+        return offset.checked_add(self.offset_shift).ok_or_else(|| {
+            PDFProcessingError::Syntax(offset, format!("Offset {} is out of range", offset))
+        });
+    }
+
+    /// Locates the `%PDF-x.y` header (7.5.2) within the first 1024 bytes, returning its offset and
+    /// the version it declares
+    fn parse_header(&mut self, buf: &[u8]) -> Result<(usize, Version), PDFProcessingError> {
+        const PREFIX: &[u8] = b"%PDF-";
+        let window = &buf[..buf.len().min(1024)];
+        let offset = window
+            .windows(PREFIX.len())
+            .position(|w| w == PREFIX)
+            .ok_or_else(|| PDFProcessingError::Syntax(0, "Missing %PDF- header".to_string()))?;
+        if offset > 0 {
+            self.warn(
+                0,
+                format!("{} bytes of junk before the %PDF- header", offset),
+            );
+        }
+
+        let start = offset + PREFIX.len();
+        let len = buf[start..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit() || **b == b'.')
+            .count();
+        let version = match Version::parse(&buf[start..start + len]) {
+            Some(version) => version,
+            None => {
+                self.warn(start, "Invalid header version, assuming 1.0".to_string());
+                Version::new(1, 0)
+            }
+        };
+
+        return Ok((offset, version));
     }
 
-    fn parse_dictionary(
+    /// Finds the offset of the last cross-reference section, checking that `startxref` points to
+    /// a cross-reference table or stream. When it does not, offsets relative to a header preceded
+    /// by junk at `header_offset` are tried and, if they fit, used from then on.
+    fn locate_xref(
         &mut self,
         buf: &[u8],
-        offset: usize,
-    ) -> Result<object::Object, PDFProcessingError> {
-        return Ok(object::Object::new(
-            object::ObjectType::Dictionary,
-            offset,
+        header_offset: usize,
+    ) -> Result<usize, PDFProcessingError> {
+        let offset = self.parse_xref_offset(buf)?;
+        if self.xref_follows(buf, offset) {
+            return Ok(offset);
+        }
+        let shifted = offset.checked_add(header_offset);
+        if header_offset > 0 && shifted.is_some_and(|shifted| self.xref_follows(buf, shifted)) {
+            self.warn(
+                header_offset,
+                "Offsets are relative to the header rather than the start of the file".to_string(),
+            );
+            self.offset_shift = header_offset;
+            return Ok(offset);
+        }
+        return Err(PDFProcessingError::Syntax(
             offset,
+            format!(
+                "startxref offset {} does not point to a cross-reference section",
+                offset
+            ),
         ));
     }
 
-    fn parse_literalstring(
+    /// Whether a cross-reference table or an indirect object starts at `offset`
+    fn xref_follows(&mut self, buf: &[u8], offset: usize) -> bool {
+        if offset >= buf.len() {
+            return false;
+        }
+        self.lexer.setp(offset);
+        self.lexer.skip_whitespace(buf);
+        if let Ok(tok) = self.lexer.peek(buf) {
+            if tok.typ == lexer::TokenType::Keyword(KeywordType::Xref) {
+                return true;
+            }
+        }
+        if self.next_integer(buf).is_err() || self.next_integer(buf).is_err() {
+            return false;
+        }
+        self.lexer.skip_whitespace(buf);
+        return self
+            .lexer
+            .next(buf)
+            .is_ok_and(|tok| tok.typ == lexer::TokenType::Keyword(KeywordType::Obj));
+    }
+
+    fn parse_dictionary<'a>(
         &mut self,
-        buf: &[u8],
+        buf: &'a [u8],
         offset: usize,
-    ) -> Result<object::Object, PDFProcessingError> {
+    ) -> Result<object::ObjectRef<'a>, PDFProcessingError> {
         self.lexer.setp(offset);
 
-        let mut size = 0;
+        let open = self.lexer.next(buf)?;
+        if open.typ != lexer::TokenType::DictionaryStart {
+            return Err(PDFProcessingError::General(format!(
+                "Expected a dictionary at offset {}",
+                open.offset
+            )));
+        }
+
+        let mut dict = object::DictionaryRef::new();
         loop {
+            self.lexer.skip_whitespace(buf);
             let tok = self.lexer.next(buf)?;
-            println!("TOken: {} {}", tok, size);
-            if tok.typ == lexer::TokenType::Delimiter(lexer::DelimiterType::RightParen) {
-                size = tok.offset + tok.lexeme.len();
+            if tok.typ == lexer::TokenType::DictionaryEnd {
                 break;
             }
+            if tok.typ != lexer::TokenType::Name {
+                return Err(PDFProcessingError::Syntax(
+                    tok.offset,
+                    format!("Expected a name as dictionary key, found {}", tok),
+                ));
+            }
+            let key = object::Name::from_lexeme(tok.lexeme);
+
+            let value = self.parse_object(buf, self.lexer.getp())?;
+
+            if dict.insert(key, value).is_some() {
+                // Last one wins, as most readers do
+                self.warn(
+                    tok.offset,
+                    format!(
+                        "Duplicate dictionary key {}",
+                        String::from_utf8_lossy(tok.lexeme)
+                    ),
+                );
+            }
         }
+
+        return Ok(object::ObjectRef::new(
+            object::ObjectRefType::Dictionary(dict),
+            open.offset,
+            self.lexer.getp() - open.offset,
+        ));
+    }
+
+    fn parse_literalstring<'a>(
+        &mut self,
+        buf: &'a [u8],
+        offset: usize,
+    ) -> Result<object::ObjectRef<'a>, PDFProcessingError> {
         self.lexer.setp(offset);
 
-        return Ok(object::Object::new(
-            object::ObjectType::String(object::StringObjectType::Literal),
-            offset,
-            size,
+        let tok = self.lexer.next(buf)?;
+        if tok.typ != lexer::TokenType::LiteralString {
+            return Err(PDFProcessingError::General(format!(
+                "Expected a literal string at offset {}",
+                tok.offset
+            )));
+        }
+
+        return Ok(object::ObjectRef::new(
+            object::ObjectRefType::String(object::StringObjectType::Literal, tok.lexeme),
+            tok.offset,
+            tok.lexeme.len(),
         ));
     }
 
-    fn parse_hexstring(
+    fn parse_hexstring<'a>(
         &mut self,
-        buf: &[u8],
+        buf: &'a [u8],
         offset: usize,
-    ) -> Result<object::Object, PDFProcessingError> {
+    ) -> Result<object::ObjectRef<'a>, PDFProcessingError> {
+        self.lexer.setp(offset);
+
+        let tok = self.lexer.next(buf)?;
+        if tok.typ != lexer::TokenType::HexString {
+            return Err(PDFProcessingError::General(format!(
+                "Expected a hexadecimal string at offset {}",
+                tok.offset
+            )));
+        }
+
+        return Ok(object::ObjectRef::new(
+            object::ObjectRefType::String(object::StringObjectType::Hexadecimal, tok.lexeme),
+            tok.offset,
+            tok.lexeme.len(),
+        ));
+    }
+
+    fn parse_nameobject<'a>(
+        &mut self,
+        buf: &'a [u8],
+        offset: usize,
+    ) -> Result<object::ObjectRef<'a>, PDFProcessingError> {
+        self.lexer.setp(offset);
+
+        let tok = self.lexer.next(buf)?;
+        if tok.typ != lexer::TokenType::Name {
+            return Err(PDFProcessingError::General(format!(
+                "Expected a name object at offset {}",
+                tok.offset
+            )));
+        }
+
+        return Ok(object::ObjectRef::new(
+            object::ObjectRefType::Name(tok.lexeme),
+            tok.offset,
+            tok.lexeme.len(),
+        ));
+    }
+
+    fn parse_array<'a>(
+        &mut self,
+        buf: &'a [u8],
+        offset: usize,
+    ) -> Result<object::ObjectRef<'a>, PDFProcessingError> {
         self.lexer.setp(offset);
 
-        let mut size = 0;
+        let open = self.lexer.next(buf)?;
+        if open.typ != lexer::TokenType::Delimiter(lexer::DelimiterType::LeftSquareBrack) {
+            return Err(PDFProcessingError::General(format!(
+                "Expected an array at offset {}",
+                open.offset
+            )));
+        }
+
+        let mut children = vec![];
         loop {
-            let tok = self.lexer.next(buf)?;
-            size += tok.lexeme.len();
-            if tok.typ == lexer::TokenType::Delimiter(lexer::DelimiterType::RightAngleBrack) {
+            self.lexer.skip_whitespace(buf);
+            let tok = self.lexer.peek(buf)?;
+            if tok.typ == lexer::TokenType::Delimiter(lexer::DelimiterType::RightSquareBrack) {
+                self.lexer.next(buf)?;
                 break;
             }
+
+            children.push(self.parse_object(buf, tok.offset)?);
         }
-        self.lexer.setp(offset);
 
-        return Ok(object::Object::new(
-            object::ObjectType::String(object::StringObjectType::Hexadecimal),
-            offset,
-            size,
+        return Ok(object::ObjectRef::new(
+            object::ObjectRefType::Array(children),
+            open.offset,
+            self.lexer.getp() - open.offset,
         ));
     }
 
-    fn parse_nameobject(
+    /// Looks ahead two tokens for an indirect reference `n g R` at `offset`, as an integer is
+    /// otherwise indistinguishable from the start of a reference. The lexer is left after the
+    /// reference if one is found.
+    fn parse_reference<'a>(
         &mut self,
-        buf: &[u8],
+        buf: &'a [u8],
         offset: usize,
-    ) -> Result<object::Object, PDFProcessingError> {
-        return Ok(object::Object::new(
-            object::ObjectType::Name,
-            offset,
-            offset,
+    ) -> Option<object::ObjectRef<'a>> {
+        self.lexer.setp(offset);
+
+        let mut start = None;
+        let mut numbers = [0; 2];
+        for number in numbers.iter_mut() {
+            self.lexer.skip_whitespace(buf);
+            let tok = self.lexer.next(buf).ok()?;
+            if tok.typ != lexer::TokenType::Regular {
+                return None;
+            }
+            match parse_number(tok.lexeme, tok.offset) {
+                Ok(object::NumericObjectType::Integer(i)) => *number = i,
+                _ => return None,
+            }
+            start.get_or_insert(tok.offset);
+        }
+
+        self.lexer.skip_whitespace(buf);
+        if self.lexer.next(buf).ok()?.typ != lexer::TokenType::Keyword(KeywordType::R) {
+            return None;
+        }
+
+        let start = start?;
+        let reference = object::Reference::new(
+            u32::try_from(numbers[0]).ok()?,
+            u16::try_from(numbers[1]).ok()?,
+        );
+        return Some(object::ObjectRef::new(
+            object::ObjectRefType::Reference(reference),
+            start,
+            self.lexer.getp() - start,
         ));
     }
 
-    fn parse_array(
+    fn parse_numericobject<'a>(
         &mut self,
-        buf: &[u8],
+        buf: &'a [u8],
         offset: usize,
-    ) -> Result<object::Object, PDFProcessingError> {
-        return Ok(object::Object::new(
-            object::ObjectType::Array,
-            offset,
-            offset,
+    ) -> Result<object::ObjectRef<'a>, PDFProcessingError> {
+        self.lexer.setp(offset);
+
+        let tok = self.lexer.next(buf)?;
+        if tok.typ != lexer::TokenType::Regular {
+            return Err(PDFProcessingError::General(format!(
+                "Expected a numeric object at offset {}",
+                tok.offset
+            )));
+        }
+
+        return Ok(object::ObjectRef::new(
+            object::ObjectRefType::Numeric(parse_number(tok.lexeme, tok.offset)?),
+            tok.offset,
+            tok.lexeme.len(),
         ));
     }
 
-    fn parse_numericobject(
+    /// Parses an array or dictionary with `parse`, unless that would nest deeper than
+    /// [`MAX_NESTING`]
+    fn parse_nested<'a>(
         &mut self,
-        buf: &[u8],
+        buf: &'a [u8],
         offset: usize,
-    ) -> Result<object::Object, PDFProcessingError> {
-        return Ok(object::Object::new(
-            object::ObjectType::Numeric(object::NumericObjectType::Integer),
-            offset,
-            offset,
-        ));
+        parse: fn(&mut PDF, &'a [u8], usize) -> Result<object::ObjectRef<'a>, PDFProcessingError>,
+    ) -> Result<object::ObjectRef<'a>, PDFProcessingError> {
+        if self.nesting >= MAX_NESTING {
+            return Err(PDFProcessingError::Syntax(
+                offset,
+                format!("Objects are nested more than {} deep", MAX_NESTING),
+            ));
+        }
+        self.nesting += 1;
+        let object = parse(self, buf, offset);
+        self.nesting -= 1;
+        return object;
     }
 
-    fn parse_object(
+    fn parse_object<'a>(
         &mut self,
-        buf: &[u8],
+        buf: &'a [u8],
         offset: usize,
-    ) -> Result<object::Object, PDFProcessingError> {
+    ) -> Result<object::ObjectRef<'a>, PDFProcessingError> {
         // Literal Strings are delimited by parenthesis: (value)
         // Hex Strings are delimited by single angle brackets: <value>
         // Name Objects are introduced with a Solidus with no delimiter between solidus and encoded
@@ -123,175 +409,1188 @@ impl PDF {
         // Arrays are delimited by square brackets: [blahblahblah]
         // Dictionaries are delimited by double angle brackets: <<blahblah>>
 
+        self.lexer.setp(offset);
+        self.lexer.skip_whitespace(buf);
         let next_token = self.lexer.peek(buf)?;
+        let offset = next_token.offset;
 
         return match next_token.typ {
-            lexer::TokenType::Delimiter(lexer::DelimiterType::LeftParen) => {
-                self.parse_literalstring(buf, offset)
-            }
-            lexer::TokenType::Delimiter(lexer::DelimiterType::LeftAngleBrack) => {
-                // Check for dictionary start
-
-                // Move lexer to after the discovered '<'
-                self.lexer.setp(next_token.offset + next_token.lexeme.len());
-                if self.lexer.next(buf)?.typ
-                    == lexer::TokenType::Delimiter(lexer::DelimiterType::LeftAngleBrack)
-                {
-                    // Found a dictionary
-                    return self.parse_dictionary(buf, offset);
-                }
-                // No dictionary found, reset the lexer pointer
-                self.lexer.setp(offset);
-                return self.parse_hexstring(buf, offset);
-            }
-            lexer::TokenType::Delimiter(lexer::DelimiterType::Solidus) => {
-                self.parse_nameobject(buf, offset)
+            lexer::TokenType::LiteralString => self.parse_literalstring(buf, offset),
+            lexer::TokenType::HexString => self.parse_hexstring(buf, offset),
+            lexer::TokenType::DictionaryStart => {
+                self.parse_nested(buf, offset, PDF::parse_dictionary)
             }
+            lexer::TokenType::Name => self.parse_nameobject(buf, offset),
             lexer::TokenType::Delimiter(lexer::DelimiterType::LeftSquareBrack) => {
-                self.parse_array(buf, offset)
+                self.parse_nested(buf, offset, PDF::parse_array)
             }
-            lexer::TokenType::Keyword(lexer::KeywordType::True) => Ok(object::Object::new(
-                object::ObjectType::Boolean(true),
-                offset,
-                offset + 4,
-            )),
-            lexer::TokenType::Keyword(lexer::KeywordType::False) => Ok(object::Object::new(
-                object::ObjectType::Boolean(false),
+            lexer::TokenType::Keyword(lexer::KeywordType::True) => {
+                self.lexer.next(buf)?;
+                Ok(object::ObjectRef::new(
+                    object::ObjectRefType::Boolean(true),
+                    offset,
+                    next_token.lexeme.len(),
+                ))
+            }
+            lexer::TokenType::Keyword(lexer::KeywordType::False) => {
+                self.lexer.next(buf)?;
+                Ok(object::ObjectRef::new(
+                    object::ObjectRefType::Boolean(false),
+                    offset,
+                    next_token.lexeme.len(),
+                ))
+            }
+            lexer::TokenType::Keyword(lexer::KeywordType::Null) => {
+                self.lexer.next(buf)?;
+                Ok(object::ObjectRef::new(
+                    object::ObjectRefType::Null,
+                    offset,
+                    next_token.lexeme.len(),
+                ))
+            }
+            lexer::TokenType::Regular => match self.parse_reference(buf, offset) {
+                Some(reference) => Ok(reference),
+                None => self.parse_numericobject(buf, offset),
+            },
+            _ => Err(PDFProcessingError::Syntax(
                 offset,
-                offset + 5,
-            )),
-            lexer::TokenType::Regular => self.parse_numericobject(buf, offset),
-            _ => Err(PDFProcessingError::General(
-                "Missing xref offset at EOF".to_string(),
+                format!(
+                    "Unexpected token {} where an object was expected",
+                    next_token
+                ),
             )),
         };
     }
 
-    pub fn parse_xref_offset(&mut self, buf: &[u8]) -> Result<usize, PDFProcessingError> {
-        let p = buf.len() - 27;
-        self.lexer.setp(p);
-        // Move pointer until `startxref`
-        loop {
-            match self.lexer.next(buf) {
-                Ok(token) => {
-                    if token.typ == lexer::TokenType::Keyword(lexer::KeywordType::Startxref) {
-                        break;
-                    }
-                }
-                Err(e) => return Err(e),
-            };
+    /// Reads the next token as an integer
+    fn next_integer(&mut self, buf: &[u8]) -> Result<i64, PDFProcessingError> {
+        self.lexer.skip_whitespace(buf);
+        let tok = self.lexer.next(buf)?;
+        if tok.typ == lexer::TokenType::Regular {
+            if let object::NumericObjectType::Integer(i) = parse_number(tok.lexeme, tok.offset)? {
+                return Ok(i);
+            }
         }
+        return Err(PDFProcessingError::Syntax(
+            tok.offset,
+            format!("Expected an integer, found {}", tok),
+        ));
+    }
 
-        self.lexer.next(buf)?; // TODO: Ensure this is a newline delimiter token
+    /// Parses the indirect object definition `num gen obj <object> endobj` at `offset` (7.3.10).
+    /// When the object is looked up through a reference, e.g. from the xref table, `expected` is
+    /// checked against the object's identity.
+    fn parse_indirect_object<'a>(
+        &mut self,
+        buf: &'a [u8],
+        offset: usize,
+        expected: Option<object::Reference>,
+    ) -> Result<(object::Reference, object::ObjectRef<'a>), PDFProcessingError> {
+        self.lexer.setp(offset);
+        self.lexer.skip_whitespace(buf);
+        let start = self.lexer.getp();
 
-        let offset_token = self.lexer.next(buf)?;
+        let num = self.next_integer(buf)?;
+        let gen = self.next_integer(buf)?;
+        let reference = match (u32::try_from(num), u16::try_from(gen)) {
+            (Ok(num), Ok(gen)) => object::Reference::new(num, gen),
+            _ => {
+                return Err(PDFProcessingError::Syntax(
+                    start,
+                    format!("Invalid object identifier {} {}", num, gen),
+                ))
+            }
+        };
 
-        if offset_token.typ != lexer::TokenType::Regular {
-            return Err(PDFProcessingError::General(
-                "Missing xref offset at EOF".to_string(),
+        self.lexer.skip_whitespace(buf);
+        let tok = self.lexer.next(buf)?;
+        if tok.typ != lexer::TokenType::Keyword(KeywordType::Obj) {
+            return Err(PDFProcessingError::Syntax(
+                tok.offset,
+                format!("Expected 'obj', found {}", tok),
             ));
         }
 
-        let mut offset: usize = 0;
-        for &byte in offset_token.lexeme {
-            if byte < b'0' || byte > b'9' {
-                return Err(PDFProcessingError::General(
-                    "Offset string is invalid ascii".to_string(),
+        if let Some(expected) = expected {
+            if expected != reference {
+                return Err(PDFProcessingError::Syntax(
+                    start,
+                    format!("Expected object {}, found object {}", expected, reference),
                 ));
             }
-            let digit = (byte - b'0') as usize;
-            offset = offset
-                .checked_mul(10)
-                .and_then(|v| v.checked_add(digit))
-                .ok_or_else(|| {
-                    PDFProcessingError::General("Offset of xref table exceeds a usize".to_string())
-                })?;
         }
 
-        return Ok(offset);
+        let mut object = self.parse_object(buf, self.lexer.getp())?;
+
+        self.lexer.skip_whitespace(buf);
+        if let Ok(tok) = self.lexer.peek(buf) {
+            if tok.typ == lexer::TokenType::Keyword(KeywordType::Stream)
+                && object.as_dict().is_some()
+            {
+                self.lexer.next(buf)?;
+                object = self.parse_stream(buf, object, tok.offset + tok.lexeme.len())?;
+                self.lexer.skip_whitespace(buf);
+            }
+        }
+
+        match self.lexer.peek(buf) {
+            Ok(tok) if tok.typ == lexer::TokenType::Keyword(KeywordType::Endobj) => {
+                self.lexer.next(buf)?;
+            }
+            _ => self.warn(
+                object.offset() + object.size(),
+                format!("Missing 'endobj' after object {}", reference),
+            ),
+        }
+
+        return Ok((reference, object));
     }
 
-    fn parse_xref_table<'a>(
+    /// Reads the data of a stream (7.3.8) given its dictionary and the offset just past the
+    /// `stream` keyword, leaving the lexer after `endstream`.
+    ///
+    /// The data is located using `/Length`, resolving it through the xref table when it is an
+    /// indirect reference. When the length is missing or does not lead to `endstream` the data is
+    /// assumed to run until the next `endstream` keyword instead.
+    fn parse_stream<'a>(
         &mut self,
         buf: &'a [u8],
-        offset: usize,
-    ) -> Result<Vec<lexer::Token<'a>>, PDFProcessingError> {
-        let mut tokens = vec![];
-        self.lexer.setp(offset);
-        loop {
-            let tok = self.lexer.next(buf)?;
-            println!("TOKEN: {}", tok);
-            if tok.typ == lexer::TokenType::Keyword(KeywordType::Trailer) {
-                break;
+        dict: object::ObjectRef<'a>,
+        keyword_end: usize,
+    ) -> Result<object::ObjectRef<'a>, PDFProcessingError> {
+        // The keyword shall be followed by CRLF or LF. Tolerate a lone CR and trailing spaces.
+        let mut start = keyword_end;
+        while buf.get(start) == Some(&b' ') {
+            start += 1;
+        }
+        match &buf[start.min(buf.len())..] {
+            [b'\r', b'\n', ..] => start += 2,
+            [b'\n', ..] => start += 1,
+            [b'\r', ..] => {
+                self.warn(start, "Stream keyword followed by a lone CR".to_string());
+                start += 1;
             }
-            tokens.push(tok)
+            _ => self.warn(
+                start,
+                "Stream keyword not followed by an end-of-line marker".to_string(),
+            ),
         }
-        println!("XREF TABLE TOKENS: {}", tokens.len());
-        return Ok(tokens);
+
+        let length = match dict.as_dict().and_then(|d| d.get("Length")) {
+            Some(length) => match length.as_reference() {
+                Some(reference) => self.resolve_length(buf, reference),
+                None => length.as_int(),
+            },
+            None => None,
+        };
+
+        let end = match length
+            .and_then(|length| usize::try_from(length).ok())
+            .and_then(|length| start.checked_add(length))
+            .filter(|&end| end <= buf.len() && endstream_follows(buf, end))
+        {
+            Some(end) => end,
+            None => {
+                self.warn(
+                    start,
+                    format!(
+                        "Stream length {:?} does not lead to 'endstream', scanning for it instead",
+                        length
+                    ),
+                );
+                scan_endstream(buf, start)?
+            }
+        };
+
+        self.lexer.setp(end);
+        self.lexer.skip_whitespace(buf);
+        self.lexer.next(buf)?; // endstream, checked above
+
+        let offset = dict.offset();
+        let dict = match dict.into_typ() {
+            object::ObjectRefType::Dictionary(dict) => dict,
+            _ => {
+                return Err(PDFProcessingError::General(format!(
+                    "Stream at offset {} is missing its dictionary",
+                    offset
+                )))
+            }
+        };
+        return Ok(object::ObjectRef::new(
+            object::ObjectRefType::Stream(object::StreamRef {
+                dict,
+                data: &buf[start..end],
+            }),
+            offset,
+            self.lexer.getp() - offset,
+        ));
+    }
+
+    /// Resolves an indirect `/Length` of a stream through the xref table
+    fn resolve_length(&mut self, buf: &[u8], reference: object::Reference) -> Option<i64> {
+        return match self.resolve(buf, reference) {
+            Ok(object) => object.as_int(),
+            Err(e) => {
+                self.warn(
+                    self.lexer.getp(),
+                    format!("Failed to resolve stream length: {}", e),
+                );
+                None
+            }
+        };
     }
 
-    fn parse_xref_stream<'a>(
+    /// Looks up the object `reference` points to through the xref table, leaving the lexer where
+    /// it was. A reference to an object number that is free or not in the table is to the null
+    /// object (7.3.10).
+    fn resolve(
+        &mut self,
+        buf: &[u8],
+        reference: object::Reference,
+    ) -> Result<object::Object, PDFProcessingError> {
+        return match self.xref.get(reference.num).copied() {
+            Some(xref::XrefEntry::Compressed { stream, index }) if reference.gen == 0 => self
+                .guard_resolving(reference, |pdf| {
+                    pdf.parse_compressed_object(buf, reference, stream, index)
+                }),
+            _ => Ok(self.resolve_ref(buf, reference)?.to_owned()),
+        };
+    }
+
+    /// Resolves `reference` to the object as it appears in `buf`, without copying it. Objects
+    /// stored in object streams only exist decoded, so cannot be resolved this way.
+    fn resolve_ref<'a>(
         &mut self,
         buf: &'a [u8],
-        offset: usize,
-    ) -> Result<Vec<lexer::Token<'a>>, PDFProcessingError> {
-        let mut tokens = vec![];
-        self.lexer.setp(offset);
-        loop {
-            let tok = self.lexer.next(buf)?;
-            println!("TOKEN: {}", tok);
-            if tok.typ == lexer::TokenType::Keyword(KeywordType::Endstream) {
-                break;
+        reference: object::Reference,
+    ) -> Result<object::ObjectRef<'a>, PDFProcessingError> {
+        return self.guard_resolving(reference, |pdf| {
+            match pdf.xref.get(reference.num).copied() {
+                Some(xref::XrefEntry::InUse { offset, .. }) => {
+                    let offset = pdf.shift(offset)?;
+                    pdf.parse_in_use_object(buf, reference, offset)
+                }
+                Some(xref::XrefEntry::Compressed { stream, .. }) if reference.gen == 0 => {
+                    Err(PDFProcessingError::General(format!(
+                        "Object {} is stored in object stream {}, so cannot be borrowed",
+                        reference, stream
+                    )))
+                }
+                _ => Ok(object::ObjectRef::new(object::ObjectRefType::Null, 0, 0)),
             }
-            tokens.push(tok)
+        });
+    }
+
+    /// Runs `resolve` for `reference`, failing on a reference cycle and restoring the lexer
+    /// position afterwards
+    fn guard_resolving<T>(
+        &mut self,
+        reference: object::Reference,
+        resolve: impl FnOnce(&mut PDF) -> Result<T, PDFProcessingError>,
+    ) -> Result<T, PDFProcessingError> {
+        if self.resolving.contains(&reference) {
+            return Err(PDFProcessingError::General(format!(
+                "Reference cycle through object {}",
+                reference
+            )));
         }
-        return Ok(vec![]);
+
+        let p = self.lexer.getp();
+        self.resolving.push(reference);
+        let object = resolve(self);
+        self.resolving.pop();
+        self.lexer.setp(p);
+
+        return object;
     }
 
-    pub fn parse_xref<'a>(
+    /// Parses the object at `offset`, which the xref table gives for `reference`. A generation
+    /// other than the one referenced means the reference is stale, and refers to the null object
+    /// (7.3.10).
+    fn parse_in_use_object<'a>(
         &mut self,
         buf: &'a [u8],
+        reference: object::Reference,
         offset: usize,
-    ) -> Result<Vec<lexer::Token<'a>>, PDFProcessingError> {
-        self.lexer.setp(offset);
-        let tok = self.lexer.next(buf)?;
-        // 7.5.4 Cross-reference table
-        if tok.typ == lexer::TokenType::Keyword(KeywordType::Xref) {
-            return self.parse_xref_table(buf, offset);
+    ) -> Result<object::ObjectRef<'a>, PDFProcessingError> {
+        let (found, object) = self.parse_indirect_object(buf, offset, None)?;
+        if found.num != reference.num {
+            return Err(PDFProcessingError::Syntax(
+                offset,
+                format!("Expected object {}, found object {}", reference, found),
+            ));
         }
-        // 7.5.8 Cross-reference streams
-        return self.parse_xref_stream(buf, offset);
+        if found.gen != reference.gen {
+            self.warn(
+                offset,
+                format!(
+                    "Reference {} is stale, object {} is at its offset",
+                    reference, found
+                ),
+            );
+            return Ok(object::ObjectRef::new(
+                object::ObjectRefType::Null,
+                offset,
+                0,
+            ));
+        }
+        return Ok(object);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
+    /// Parses the `index`th object of the object stream with object number `stream`
+    fn parse_compressed_object(
+        &mut self,
+        buf: &[u8],
+        reference: object::Reference,
+        stream: u32,
+        index: u32,
+    ) -> Result<object::Object, PDFProcessingError> {
+        let object_stream = self.object_stream(buf, stream)?;
+        let (num, offset) = *object_stream.objects.get(index as usize).ok_or_else(|| {
+            PDFProcessingError::General(format!(
+                "Object stream {} has no object at index {}",
+                stream, index
+            ))
+        })?;
+        if num != reference.num {
+            return Err(PDFProcessingError::General(format!(
+                "Expected object {} at index {} of object stream {}, found object {}",
+                reference, index, stream, num
+            )));
+        }
 
-    use base64::{engine::general_purpose, Engine as _};
+        let object = self.parse_object(&object_stream.data, offset)?;
+        return Ok(object.to_owned());
+    }
 
-    static  TAIL_PDF_B64: &'static str= "vfNy9A5L0NttRG/9H3qLeuhNzqM3ioFebwZ6bcmvUQq96n70SuK/fF/0MuK15DT0YuK3sMQipHc+mTl3DfScJdCzl0XP0gI9o8ySbiJ66lfoKf6gJyuInlj8hkksvtxPnHHoMReiy3eP9vs72s9saF/noH2S/fsQ2tsaaKGlaF49NKcPmnkFTfuA9lL2z+ahPV6A9mA/2t0paLeGoF3finZlG9pFOTv3Ce1MXLST2dGOfkM7JDX756PJ74+2W3R2dkbbtg9t82e0jSXQ1g1HW3MZbVVqtOVd0ZZIr4U+2vx8aHNGos28ijY9DG2K9Jko2uNeoY0pgDayP9qwvWiDRW9QFbT+DdH69EbrORatm/jtrNA6JkZrXwatjZy1PIHWrBVaY9GrnwWtdmm0anPRqkpuZeHpIVGE+qUgNPSgvLcj1OcnoV5ZCXVvQqhzV0KRwtRthOVbliXUbAihxp8INRSOr7uIUK1BhKpLXdVIQpVXE6pwiFA5CJXaRqi45BapSKhgXkL56xDKE59QriSEcsg+azVCmZoTSi96aSQvxQNCyUoQSrycUNgSQvK3CsWWWPRvBH+EyX8eIPi2m+BzZYIPSwneCmuHhF29pwSOML15gkBbTyD/l4OnIwke9SW4P4/gzmSCm8L712S9LGcXZhGcFe4+Jc+JeARHMxEcEs7eL4y+5wnBbuH5HcLIW38SbLpFsH4swb/C2iu3ECyV+OIMBPNEd/ZKghmTCKYKk0+SHuOzEIwpQzDiBsHQhgT/5CMYkJCgbxWCXuKne3OCLgkIOtQjaJeLoHUYQQt5Gj8naFCVoK4wdq3CBNUmElTpTlBRuLvsToJSolFc8gqfJIgQ3XwbCP76QhB+myDrJYJMwvLynR+kkXtI+ZIg2UWCxOI9rD1B/IwEsUsQRL+M/ycK/4ew9tfm+B+P4b8rgv+6Cb43C9/+gW+cx381D/95TPzHcvYgFv5tE//GNfyrZ/EvyXpe2PxsBP7Jd/jHhX8Pa/gH7uLvk9yoNPg7L+Bvk7zNwsEbpMfaBfirH+Ov6Iu/RDQXnsGfdwt/dkn8GaI9VXh64jP8cePxR4/FH3Ebf+hq/H/G4feX2j6L8XvOwO82DL+z5Efa+G3347cSr80t/CYP8BuKn7qV8WuKTrXX+FVG41fsiF/WwS8lHF5sMH4hA79AHvy8/fD/Es3wjPjZwvAzvcVPJzWpx+CnWISfVHQSyWwJbuLHS4wfuwF+DLmvaNHxflXH+14B70slvI858N6lxXtdG8+Phec4eOZNPE3Y+cVcvKf/4j3aj3dP9neEWW98xbuWBe+y8PEF4fqzB/FOHcI7HoV3RBj6oHD8PnmPOoe36x3eDmHkrQ3xNs3AW38M79+reKvD8JZfwpN79BYOwpvXHW+28PKM/nhTK+JNGoo3/jLemFN4I4Xfh0uPweJp4FO8ftK39z68Hhfxukr/TsLwkdvw2hp4rU7iNZd+TaRHQ+HqesLptYXNa2TGq/obr3I4XoXSeGXFf6kkeMVl3iLyXrAGXv72eHkq4+V8iJd9JF6WwXgZ++KlS4OXSvg9qWj9bwteQpk3nrzHklnlW9X9kw/3ZyXcr8Lbn47jvu+O+1rh+sLbqgCuKeytTcZ9UR73iTDrwz24917g3j6Ge2Mz7tW7uJcu456X+JkWuCc74R4T/j/cBffAaty9wty7pe9O4eLtqXG3SN5G4e11SXBXC6uvTIW7LBPuotu4803cOadwZ/bAnZYId9IX3PFncMcMwB05EXdYK9zBYbgDwe0rbN0rO263arhyh27kUdy2MXFb6rjNRKvRDtz6Z3HriN+aS3GrebhV7uNWlLnKS16ZCNySuXGLFcQtdA63wEbcvIdx/1qCK98FbnZh+yxbcDNOw00nc6e+gZviIW5S8fS/WbgJRS/eDNzYj3FjPMGNtg71S9j4u7Dsl+2oj+9R7/+HepMG5Z9AKeFxqzpK74d6Kcz7DNRjiT0IQ90VTr8p7H/tJepKDtTFwqizb1CnAtRxYdkjS1EHb6D2PUNF7UDtWo3aLsy7RVh7Yy3Uukqo1T5qZX7UMtkvboxa0AYl/KRm1URNF60p2VATZR2XHjXqCWr4LtQQ4ehBJmpAEVTfLKhewv3dhfm7ZEB1TIVqnwTV+jmqxQtUU6lptBNV/yGqzi1Uzcuoav+gqjRCVRTWLzccVVpmLzEZVbQ4qmA+VD7Z/yX14a1Q2dqiMidApRfdNONRKbuhko1FJW6HCsuKivcTFScWKoZwfbQBOL9q43x7ifP5O87HVDjvuuKE5Nw9hmMK/2pTcZ4bOE8l9tDBuSfx28LIN4SVr+bCuZQR5zw4py2cE2twjgoHH3qBs184d88fnP/e4ey4i7O1C86mQTjrK+H8Wwtn1VycZRNwFg/GWSA8PTcrzsz8OFOEqSd8xRkzDWek8P4wqRksOQPr4/STnF6ROF2f4XTqjxPZB6dtEZyWl3CaVcVpJKxd7yJObfFRQ/z9XQOnsmiVX4RTRjRKjsIpJnWFm+FEdMfJVx4ndxhOuORnFb+ZluCkF09pquCkLIqTrB1OYpk34WeceOItlvSOLvz8R7j2Z23sr8KpH2djvxN+fd0G29+BrVZhW4ew9S7YL4XnnyXFfiTcfl+4/s4Z7JuNsa9VwL4kjH7exz6zDPtkGPZR4dyDH7D32dhRR7F3utjbFmILK9kblmCv3Yy9egP2CuH1pQWxFwn/zxcmniPcPDMu9rQy2JOF48efxh4jtaOEo4eLr8GiOVD89JuP3Vu4v0cN7C4Bdsdp2O0zYLcehd1cdJusx24oMeElu9Yj7OrnsKu2w654DbucMH3pztglxGtR0SmUFbtAIuy8wv5/NcTO8RU760/szFmw00uvtBJLBXYy0U0sNWEZseP9wI4THztmSmyEx383wPrRCutrBayPX7HeCcuGzmJ5h7Ec4VbzC5YmHP1SWPlZHqzHwrQP2mLdbYx1S9j1ujyXf2Kdn4F1+hvW8TdYR6XHwbtY+8tgRa3B2iU8v20W1uaiWBuKY639H9aq3VjLhcsXP8ZaIBw8V7h4pok1PTXWlFRYEyysseJntHD68HtYQ4SXB63D6t8Bq/d3rJ5gdRVvncVPZIDVLjlWqztYzd9iNYnEahgdq670rS0sXyM7VlXxVqk2VnmZs0x5rBIXsIpKfSHh9gI3sPJKn1wfsHKITtZHWJk+YqUX/TQHsFJI36QhrMQ1scJk7vhRWHFkrpjSjzmYv19g/tiL+bUX5qfqmO8rYL4R9g3iYCphdOsJppEE81V7zOfCu48DzAdPMe8J394Wxr4uPHzFxLxoYZ6T9UwizBPC18fSYx4WXj8grL9Xnv+EtXeIztbZmJuEldcJ56+Jwlwh6zKpWfQFc76POVdqZmXHnLYWc/IkzPHPMMdKfKQ8Q69j/iN6Ax5j9n2I2VvOutfB7Dwes0MNzHZS2+owZvOCmI2vYDaIj1lnP2ZN6VOtFGZlOatwELOssHjpMpglhNOL/MYsJD0KVMTMIzz+VwbM8GSYWWX2zBLPMB8z7T3MVH0xkyfFTJIcM+wCZvwbmHHGYMZsgInw6u+KGD/yYnz5gvEpHsb7HBhv5CzojeGux7DHYRhbMV6txXi+D+OJMPjDSxj3jmPcrolxXdj8yi6Mi6Uxzs3EOD0K44TkHAvHOCyMfmAOxt4LGLuFw3dGYWxPhLElDcbGJBjrMmCsEZ2VwsfLLIzFrzAW6Bhzb2DMuooxXfSmHMaY0AdjjOiPkPjQ+xiD42AMeIPRdxJGr54Y3RthdCmK0bEZRvsBGG0OYrQ8jdFsGUbjERgN2mHU7Y5RKxKjusSrZseouBej3BGM0hMwSvTHKCqsXlBmyv8OI49weq7fGDluY2QVz5nEYzrh9NRyRynEW7JMGIn7YoSdwkggM8QVf7FOYkR/gv5nAfqPcuhfXPSPA9HfLUOXbx09EK5VddDNVeivQujPy6M/kfjDc+j3E6HfEba9mRH96ib0S8LQF/Kgn52KfspCP5Eb/ahw7yGJHRAO3jsUfbeGvkt0tq9A3xoTfVNv9PUP0NemRV/dA33FYfSlBvriNOgLKqHPrYo+qw/6tKvok/eiTxCNsWfRR51BHy7vQz6jDxL+7i+5fVKh96iG3rUTeifh58jR6G1nordajN68GHrjHegNPPS6A9BrSX71LOhVVqJXFP/lmqKXFq8l+qEXFb+FJJZfeueRmf+KQA/PhJ4tO3rmCugZZJa0PdFTXURPbqMnTY3+P/GbUGLx5H5id0OPMQJdvnu0Xxraj/hoXwajfZT9O2HsNxFowRg0txia3QTNiEJ79QTtheyfDkV7JPx7fyXaHeH6m23RrgkDXxauviBnZ5+hnXqDdiIB2hHh7INSs28Ymvz+aP+Jzo7aaFtXoG16jrYhE9raSLTVu9FW/EFbVhdtsfRacAdtXnK02R3RZuxBm/oRbbL0mSDaYy+ijU6JNqI52tDlaP+I3sA8aP1KovVujNZD+L+r+O10Ey3yC1q7bGit5azFRrSmldAaiV69uGi1sqL9PQStivSvJDw9LNn/AcYiApcKZW5kc3RyZWFtCmVuZG9iagpzdGFydHhyZWYKMTU1NjMwMjcKJSVFT0YK";
+    /// Decodes the object stream (7.5.7) with object number `num` and reads its header of object
+    /// numbers and offsets, caching the result
+    fn object_stream(
+        &mut self,
+        buf: &[u8],
+        num: u32,
+    ) -> Result<Rc<ObjectStream>, PDFProcessingError> {
+        if let Some(object_stream) = self.object_streams.get(&num) {
+            return Ok(Rc::clone(object_stream));
+        }
 
-    #[test]
-    fn test_xref_offset() {
-        let tail_pdf = general_purpose::STANDARD
-            .decode(TAIL_PDF_B64)
-            .expect("Failed to decode b64 string");
+        let (offset, gen) = match self.xref.get(num) {
+            Some(xref::XrefEntry::InUse { offset, gen }) => (self.shift(*offset)?, *gen),
+            _ => {
+                return Err(PDFProcessingError::General(format!(
+                    "Object stream {} is not in use",
+                    num
+                )))
+            }
+        };
+        let (reference, object) =
+            self.parse_indirect_object(buf, offset, Some(object::Reference::new(num, gen)))?;
+        let stream = object.as_stream().ok_or_else(|| {
+            PDFProcessingError::Syntax(offset, format!("Object {} is not a stream", reference))
+        })?;
+        if stream
+            .dict
+            .get_name("Type")
+            .is_none_or(|typ| typ != "ObjStm")
+        {
+            self.warn(
+                offset,
+                format!("Object stream {} is not of /Type /ObjStm", reference),
+            );
+        }
 
-        let lxr = lexer::Lexer::new();
-        let mut pdf = PDF { lexer: lxr };
+        let invalid = |key: &str| {
+            PDFProcessingError::Syntax(
+                offset,
+                format!("Object stream {} has an invalid /{}", reference, key),
+            )
+        };
+        let n = stream
+            .dict
+            .get_int("N")
+            .and_then(|n| usize::try_from(n).ok())
+            .ok_or_else(|| invalid("N"))?;
+        let first = stream
+            .dict
+            .get_int("First")
+            .and_then(|first| usize::try_from(first).ok())
+            .ok_or_else(|| invalid("First"))?;
 
-        let offset = pdf
-            .parse_xref_offset(&tail_pdf)
-            .expect("Failed to find xref offset");
-        assert_eq!(offset, 15563027)
-    }
+        let data = decode_structural_stream(stream, reference)?.into_owned();
+        if first > data.len() {
+            return Err(invalid("First"));
+        }
 
-    #[test]
-    fn test_parse_literalstring() {
-        let lxr = lexer::Lexer::new();
-        let mut pdf = PDF { lexer: lxr };
+        // The header holds N pairs of object number and offset relative to First
+        self.lexer.setp(0);
+        let mut objects = Vec::with_capacity(n.min(data.len()));
+        for _ in 0..n {
+            let num = self.next_integer(&data[..first])?;
+            let relative = self.next_integer(&data[..first])?;
+            match (u32::try_from(num), usize::try_from(relative)) {
+                (Ok(num), Ok(relative)) if first + relative < data.len() => {
+                    objects.push((num, first + relative))
+                }
+                _ => {
+                    return Err(PDFProcessingError::Syntax(
+                        offset,
+                        format!(
+                            "Object stream {} has an invalid entry {} {}",
+                            reference, num, relative
+                        ),
+                    ))
+                }
+            }
+        }
+
+        let object_stream = Rc::new(ObjectStream { data, objects });
+        self.object_streams.insert(num, Rc::clone(&object_stream));
+        return Ok(object_stream);
+    }
+
+    /// Reads the byte offset of the last cross-reference section after the `startxref` keyword
+    /// (7.5.5). The keyword is searched for backwards over the last 1024 bytes, tolerating
+    /// trailing bytes after `%%EOF`.
+    pub fn parse_xref_offset(&mut self, buf: &[u8]) -> Result<usize, PDFProcessingError> {
+        const KEYWORD: &[u8] = b"startxref";
+        let start = buf.len().saturating_sub(1024);
+        let position = buf[start..]
+            .windows(KEYWORD.len())
+            .rposition(|w| w == KEYWORD)
+            .ok_or_else(|| {
+                PDFProcessingError::Syntax(
+                    start,
+                    "Missing 'startxref' near the end of the file".to_string(),
+                )
+            })?;
+
+        self.lexer.setp(start + position + KEYWORD.len());
+        self.lexer.skip_whitespace(buf);
+        let offset_token = self.lexer.next(buf)?;
+
+        if offset_token.typ != lexer::TokenType::Regular {
+            return Err(PDFProcessingError::General(
+                "Missing xref offset at EOF".to_string(),
+            ));
+        }
+
+        let mut offset: usize = 0;
+        for &byte in offset_token.lexeme {
+            if !byte.is_ascii_digit() {
+                return Err(PDFProcessingError::General(
+                    "Offset string is invalid ascii".to_string(),
+                ));
+            }
+            let digit = (byte - b'0') as usize;
+            offset = offset
+                .checked_mul(10)
+                .and_then(|v| v.checked_add(digit))
+                .ok_or_else(|| {
+                    PDFProcessingError::General("Offset of xref table exceeds a usize".to_string())
+                })?;
+        }
+
+        return Ok(offset);
+    }
+
+    /// Reads the entry `offset gen n|f` of a cross-reference table at the lexer position, leaving
+    /// the lexer untouched if there is none
+    fn parse_xref_entry(&mut self, buf: &[u8]) -> Option<xref::XrefEntry> {
+        let p = self.lexer.getp();
+        let entry = (|| {
+            let first = self.next_integer(buf).ok()?;
+            let gen = u16::try_from(self.next_integer(buf).ok()?).ok()?;
+            self.lexer.skip_whitespace(buf);
+            return match self.lexer.next(buf).ok()?.typ {
+                lexer::TokenType::Keyword(KeywordType::N) => Some(xref::XrefEntry::InUse {
+                    offset: usize::try_from(first).ok()?,
+                    gen,
+                }),
+                lexer::TokenType::Keyword(KeywordType::F) => Some(xref::XrefEntry::Free {
+                    next: u32::try_from(first).ok()?,
+                    gen,
+                }),
+                _ => None,
+            };
+        })();
+        if entry.is_none() {
+            self.lexer.setp(p);
+        }
+        return entry;
+    }
+
+    /// Parses a cross-reference table (7.5.4) starting with the `xref` keyword at `offset`, leaving
+    /// the lexer at the `trailer` keyword.
+    ///
+    /// Entries are read token by token rather than as fixed 20 byte lines, so entries ending in a
+    /// single byte end-of-line marker are accepted. A missing subsection header continues the
+    /// numbering of the previous subsection, and a subsection wrongly starting at 1 with the
+    /// head of the free list is renumbered to start at 0.
+    fn parse_xref_table(
+        &mut self,
+        buf: &[u8],
+        offset: usize,
+    ) -> Result<xref::XrefTable, PDFProcessingError> {
+        self.lexer.setp(offset);
+        self.lexer.skip_whitespace(buf);
+        let tok = self.lexer.next(buf)?;
+        if tok.typ != lexer::TokenType::Keyword(KeywordType::Xref) {
+            return Err(PDFProcessingError::Syntax(
+                tok.offset,
+                format!("Expected 'xref', found {}", tok),
+            ));
+        }
+
+        let mut table = xref::XrefTable::new();
+        let mut next_num: u32 = 0;
+        loop {
+            self.lexer.skip_whitespace(buf);
+            let tok = match self.lexer.peek(buf) {
+                Ok(tok) if tok.typ == lexer::TokenType::Regular => tok,
+                _ => break, // trailer, or a malformed end that the trailer lookup reports
+            };
+
+            let (mut start, count) = match self.parse_xref_entry(buf) {
+                Some(_) => {
+                    self.warn(tok.offset, "Missing xref subsection header".to_string());
+                    self.lexer.setp(tok.offset);
+                    (next_num, None)
+                }
+                None => {
+                    let start = self.next_integer(buf)?;
+                    let count = self.next_integer(buf)?;
+                    let start = u32::try_from(start).map_err(|_| {
+                        PDFProcessingError::Syntax(
+                            tok.offset,
+                            format!("Invalid xref subsection start {}", start),
+                        )
+                    })?;
+                    (start, Some(count))
+                }
+            };
+
+            let mut entries = vec![];
+            while let Some(entry) = self.parse_xref_entry(buf) {
+                entries.push(entry);
+            }
+
+            if let Some(count) = count {
+                if count != entries.len() as i64 {
+                    self.warn(
+                        tok.offset,
+                        format!(
+                            "Xref subsection declares {} entries but has {}",
+                            count,
+                            entries.len()
+                        ),
+                    );
+                }
+            }
+
+            // Object 0 is always the head of the free list with generation 65535
+            if start == 1
+                && entries.first()
+                    == Some(&xref::XrefEntry::Free {
+                        next: 0,
+                        gen: 65535,
+                    })
+            {
+                self.warn(
+                    tok.offset,
+                    "Xref subsection starts at 1 instead of 0".to_string(),
+                );
+                start = 0;
+            }
+
+            next_num = start.saturating_add(entries.len() as u32);
+            for (num, entry) in (start..).zip(entries) {
+                table.insert(num, entry);
+            }
+        }
+
+        return Ok(table);
+    }
+
+    /// Parses the trailer (7.5.5) following a cross-reference table, the lexer being positioned
+    /// before the `trailer` keyword
+    fn parse_trailer(&mut self, buf: &[u8]) -> Result<object::Dictionary, PDFProcessingError> {
+        self.lexer.skip_whitespace(buf);
+        let tok = self.lexer.next(buf)?;
+        if tok.typ != lexer::TokenType::Keyword(KeywordType::Trailer) {
+            return Err(PDFProcessingError::Syntax(
+                tok.offset,
+                format!("Expected 'trailer', found {}", tok),
+            ));
+        }
+
+        let trailer = self.parse_object(buf, self.lexer.getp())?;
+        return match trailer.as_dict() {
+            Some(dict) => Ok(dict.to_owned()),
+            None => Err(PDFProcessingError::Syntax(
+                trailer.offset(),
+                "Expected a trailer dictionary".to_string(),
+            )),
+        };
+    }
+
+    /// Parses the cross-reference stream (7.5.8) whose indirect object starts at `offset`,
+    /// returning its entries and its dictionary, which doubles as the trailer
+    fn parse_xref_stream(
+        &mut self,
+        buf: &[u8],
+        offset: usize,
+    ) -> Result<(xref::XrefTable, object::Dictionary), PDFProcessingError> {
+        let (reference, object) = self.parse_indirect_object(buf, offset, None)?;
+        let stream = object.as_stream().ok_or_else(|| {
+            PDFProcessingError::Syntax(
+                offset,
+                format!(
+                    "Expected a cross-reference stream, found object {}",
+                    reference
+                ),
+            )
+        })?;
+        let dict = &stream.dict;
+        if dict.get_name("Type").is_none_or(|typ| typ != "XRef") {
+            self.warn(
+                offset,
+                format!("Cross-reference stream {} is not of /Type /XRef", reference),
+            );
+        }
+
+        let invalid = |message: &str| {
+            PDFProcessingError::Syntax(
+                offset,
+                format!("Cross-reference stream {} {}", reference, message),
+            )
+        };
+
+        let widths = dict
+            .get_array("W")
+            .and_then(|w| w.iter().map(|v| v.as_int()).collect::<Option<Vec<i64>>>())
+            .and_then(|w| {
+                w.into_iter()
+                    .map(|v| usize::try_from(v).ok())
+                    .collect::<Option<Vec<usize>>>()
+            })
+            .filter(|w| w.len() == 3 && w.iter().all(|&v| v <= 8))
+            .ok_or_else(|| invalid("has an invalid /W"))?;
+
+        // Pairs of first object number and entry count, defaulting to [0 Size]
+        let index = match dict.get_array("Index") {
+            Some(index) => index
+                .iter()
+                .map(|v| v.as_int().and_then(|v| u32::try_from(v).ok()))
+                .collect::<Option<Vec<u32>>>()
+                .filter(|index| index.len() % 2 == 0)
+                .ok_or_else(|| invalid("has an invalid /Index"))?,
+            None => {
+                let size = dict
+                    .get_int("Size")
+                    .and_then(|v| u32::try_from(v).ok())
+                    .ok_or_else(|| invalid("is missing /Size"))?;
+                vec![0, size]
+            }
+        };
+
+        let data = decode_structural_stream(stream, reference)?;
+        let row_len: usize = widths.iter().sum();
+        let mut rows = data.chunks_exact(row_len.max(1));
+
+        let mut table = xref::XrefTable::new();
+        'sections: for section in index.chunks(2) {
+            for num in section[0]..section[0].saturating_add(section[1]) {
+                let Some(row) = rows.next() else {
+                    self.warn(
+                        offset,
+                        format!("Cross-reference stream {} data is truncated", reference),
+                    );
+                    break 'sections;
+                };
+
+                let mut fields = [0u64; 3];
+                let mut row = row;
+                for (field, &width) in fields.iter_mut().zip(widths.iter()) {
+                    *field = row[..width]
+                        .iter()
+                        .fold(0, |acc, &byte| acc << 8 | byte as u64);
+                    row = &row[width..];
+                }
+                // The type defaults to 1 when its field is absent
+                if widths[0] == 0 {
+                    fields[0] = 1;
+                }
+
+                let entry = match fields[0] {
+                    0 => u32::try_from(fields[1])
+                        .ok()
+                        .zip(u16::try_from(fields[2]).ok())
+                        .map(|(next, gen)| xref::XrefEntry::Free { next, gen }),
+                    1 => usize::try_from(fields[1])
+                        .ok()
+                        .zip(u16::try_from(fields[2]).ok())
+                        .map(|(offset, gen)| xref::XrefEntry::InUse { offset, gen }),
+                    2 => u32::try_from(fields[1])
+                        .ok()
+                        .zip(u32::try_from(fields[2]).ok())
+                        .map(|(stream, index)| xref::XrefEntry::Compressed { stream, index }),
+                    _ => continue, // Other types are references to the null object
+                };
+                match entry {
+                    Some(entry) => {
+                        table.insert(num, entry);
+                    }
+                    None => self.warn(
+                        offset,
+                        format!(
+                            "Cross-reference stream {} entry for object {} is out of range",
+                            reference, num
+                        ),
+                    ),
+                }
+            }
+        }
+
+        return Ok((table, stream.dict.to_owned()));
+    }
+
+    /// Parses the cross-reference section, table and trailer or stream, at `offset`
+    fn parse_xref_section(
+        &mut self,
+        buf: &[u8],
+        offset: usize,
+    ) -> Result<(xref::XrefTable, object::Dictionary), PDFProcessingError> {
+        let offset = self.shift(offset)?;
+        self.lexer.setp(offset);
+        self.lexer.skip_whitespace(buf);
+        let tok = self.lexer.next(buf)?;
+        // 7.5.4 Cross-reference table
+        if tok.typ == lexer::TokenType::Keyword(KeywordType::Xref) {
+            let mut table = self.parse_xref_table(buf, offset)?;
+            let trailer = self.parse_trailer(buf)?;
+
+            // 7.5.8.4 Hybrid-reference files: the entries of the table come first, then those of
+            // the cross-reference stream, which also replace entries the table marks free for
+            // readers unaware of object streams
+            if let Some(value) = trailer.get_int("XRefStm") {
+                let stream = usize::try_from(value)
+                    .ok()
+                    .and_then(|o| self.shift(o).ok())
+                    .filter(|&o| o < buf.len())
+                    .ok_or_else(|| {
+                        PDFProcessingError::General(format!("Invalid /XRefStm offset {}", value))
+                    })
+                    .and_then(|o| self.parse_xref_stream(buf, o));
+                match stream {
+                    Ok((stream, _)) => {
+                        for (num, entry) in stream.iter() {
+                            if table
+                                .get(num)
+                                .is_none_or(|e| matches!(e, xref::XrefEntry::Free { .. }))
+                            {
+                                table.insert(num, *entry);
+                            }
+                        }
+                    }
+                    Err(err) => self.warn(
+                        offset,
+                        format!("Failed to parse /XRefStm cross-reference stream: {}", err),
+                    ),
+                }
+            }
+
+            return Ok((table, trailer));
+        }
+        // 7.5.8 Cross-reference streams
+        return self.parse_xref_stream(buf, offset);
+    }
+
+    /// Parses the cross-reference section at `offset` and the older sections of incremental
+    /// updates (7.5.6) reached through `/Prev`, returning the merged entries, newest first, and
+    /// the newest trailer.
+    ///
+    /// An older section that cannot be parsed, or a `/Prev` that loops back to a section already
+    /// read, ends the chain with a warning.
+    pub fn parse_xref(
+        &mut self,
+        buf: &[u8],
+        offset: usize,
+    ) -> Result<(xref::XrefTable, object::Dictionary), PDFProcessingError> {
+        let (mut table, trailer) = self.parse_xref_section(buf, offset)?;
+
+        let mut visited = HashSet::from([offset]);
+        let mut prev = trailer.get_int("Prev");
+        while let Some(value) = prev {
+            let Some(offset) = usize::try_from(value)
+                .ok()
+                .filter(|&o| self.shift(o).is_ok_and(|o| o < buf.len()))
+            else {
+                self.warn(offset, format!("Invalid /Prev offset {}", value));
+                break;
+            };
+            if !visited.insert(offset) {
+                self.warn(offset, format!("/Prev loops back to offset {}", offset));
+                break;
+            }
+
+            match self.parse_xref_section(buf, offset) {
+                Ok((older, older_trailer)) => {
+                    table.merge_older(older);
+                    prev = older_trailer.get_int("Prev");
+                }
+                Err(err) => {
+                    self.warn(
+                        offset,
+                        format!("Failed to parse previous cross-reference section: {}", err),
+                    );
+                    break;
+                }
+            }
+        }
+
+        return Ok((table, trailer));
+    }
+
+    /// Rebuilds the cross-reference table of a damaged file by scanning it for indirect object
+    /// definitions and trailers, returning the entries and a trailer made of the `/Root`,
+    /// `/Info`, `/ID` and `/Encrypt` found.
+    ///
+    /// Later definitions of an object number win, as they would through incremental updates.
+    /// Objects of object streams are added unless defined directly, and the catalog is looked
+    /// for when no trailer names it.
+    fn reconstruct_xref(
+        &mut self,
+        buf: &[u8],
+    ) -> Result<(xref::XrefTable, object::Dictionary), PDFProcessingError> {
+        const TRAILER_KEYS: [&str; 4] = ["Root", "Info", "ID", "Encrypt"];
+
+        self.offset_shift = 0;
+        self.object_streams.clear();
+        let headers = scan_object_headers(buf);
+
+        // Provisional entries for indirect stream lengths while the definitions are checked
+        self.xref = xref::XrefTable::new();
+        for &(reference, offset) in &headers {
+            self.xref.insert(
+                reference.num,
+                xref::XrefEntry::InUse {
+                    offset,
+                    gen: reference.gen,
+                },
+            );
+        }
+
+        let mut table = xref::XrefTable::new();
+        let mut trailer = object::Dictionary::new();
+        let mut catalog = None;
+        let mut object_streams = vec![];
+        for &(reference, offset) in &headers {
+            let Ok((_, object)) = self.parse_indirect_object(buf, offset, Some(reference)) else {
+                continue;
+            };
+            table.insert(
+                reference.num,
+                xref::XrefEntry::InUse {
+                    offset,
+                    gen: reference.gen,
+                },
+            );
+
+            let Some(dict) = object.as_dict() else {
+                continue;
+            };
+            match dict.get_name("Type") {
+                Some(typ) if typ == "Catalog" => catalog = Some(reference),
+                Some(typ) if typ == "ObjStm" => object_streams.push(reference.num),
+                Some(typ) if typ == "XRef" => {
+                    for key in TRAILER_KEYS {
+                        if let Some(value) = dict.get(key) {
+                            trailer.insert(key, value.to_owned());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if table.is_empty() {
+            return Err(PDFProcessingError::Syntax(
+                0,
+                "No indirect objects found while repairing the file".to_string(),
+            ));
+        }
+
+        for (position, _) in buf
+            .windows(b"trailer".len())
+            .enumerate()
+            .filter(|(_, w)| *w == b"trailer")
+        {
+            let Ok(object) = self.parse_object(buf, position + b"trailer".len()) else {
+                continue;
+            };
+            if let Some(dict) = object.as_dict() {
+                for key in TRAILER_KEYS {
+                    if let Some(value) = dict.get(key) {
+                        trailer.insert(key, value.to_owned());
+                    }
+                }
+            }
+        }
+
+        self.xref = table;
+        let mut compressed = vec![];
+        for stream in object_streams {
+            let Ok(object_stream) = self.object_stream(buf, stream) else {
+                continue;
+            };
+            for (index, &(num, _)) in object_stream.objects.iter().enumerate() {
+                if self.xref.get(num).is_none() {
+                    let entry = xref::XrefEntry::Compressed {
+                        stream,
+                        index: index as u32,
+                    };
+                    self.xref.insert(num, entry);
+                    compressed.push(object::Reference::new(num, 0));
+                }
+            }
+        }
+
+        if trailer.get_ref("Root").is_none() {
+            if catalog.is_none() {
+                catalog = compressed.into_iter().find(|&reference| {
+                    self.resolve(buf, reference).is_ok_and(|object| {
+                        object
+                            .as_dict()
+                            .and_then(|dict| dict.get_name("Type"))
+                            .is_some_and(|typ| typ == "Catalog")
+                    })
+                });
+            }
+            match catalog {
+                Some(catalog) => {
+                    trailer.insert("Root", catalog);
+                }
+                None => self.warn(0, "No document catalog found while repairing".to_string()),
+            }
+        }
+
+        let size = self.xref.iter().last().map_or(0, |(num, _)| num as i64 + 1);
+        trailer.insert("Size", size);
+        self.warn(
+            0,
+            format!(
+                "Rebuilt the cross-reference table from {} objects",
+                self.xref.len()
+            ),
+        );
+
+        return Ok((std::mem::take(&mut self.xref), trailer));
+    }
+}
+
+/// Decodes an object or cross-reference stream, whose data the parser reads itself and so cannot
+/// be left encoded with an image codec
+fn decode_structural_stream<'a>(
+    stream: &object::StreamRef<'a>,
+    reference: object::Reference,
+) -> Result<Cow<'a, [u8]>, PDFProcessingError> {
+    let decoded = stream.decode()?;
+    if let Some(codec) = decoded.image_codec {
+        return Err(PDFProcessingError::General(format!(
+            "Stream {} is encoded with the image codec {:?}",
+            reference, codec.filter
+        )));
+    }
+    return Ok(decoded.data);
+}
+
+/// Whether `endstream` is the next keyword at `offset`, allowing for the end-of-line marker that
+/// precedes it
+fn endstream_follows(buf: &[u8], offset: usize) -> bool {
+    let rest = &buf[offset..];
+    let eol = rest
+        .iter()
+        .take_while(|b| lexer::WhitespaceType::try_from(*b).is_ok())
+        .count();
+    return rest[eol..].starts_with(b"endstream");
+}
+
+/// Finds the `num gen obj` headers of indirect object definitions by scanning the whole buffer,
+/// returning each object's identity and offset in the order they appear
+fn scan_object_headers(buf: &[u8]) -> Vec<(object::Reference, usize)> {
+    let is_whitespace = |b: &u8| lexer::WhitespaceType::try_from(b).is_ok();
+    let is_boundary = |b: &u8| is_whitespace(b) || lexer::DelimiterType::try_from(b).is_ok();
+    let count_back = |end: usize, pred: &dyn Fn(&u8) -> bool| {
+        return buf[..end].iter().rev().take_while(|b| pred(b)).count();
+    };
+
+    let mut headers = vec![];
+    for (keyword, _) in buf.windows(3).enumerate().filter(|(_, w)| *w == b"obj") {
+        if !buf.get(keyword + 3).is_none_or(is_boundary) {
+            continue;
+        }
+        let gen_end = keyword - count_back(keyword, &is_whitespace);
+        let gen_start = gen_end - count_back(gen_end, &|b| b.is_ascii_digit());
+        let num_end = gen_start - count_back(gen_start, &is_whitespace);
+        let num_start = num_end - count_back(num_end, &|b| b.is_ascii_digit());
+        if gen_end == keyword
+            || gen_start == gen_end
+            || num_end == gen_start
+            || num_start == num_end
+            || (num_start > 0 && !is_boundary(&buf[num_start - 1]))
+        {
+            continue;
+        }
+
+        let num = std::str::from_utf8(&buf[num_start..num_end]).ok();
+        let gen = std::str::from_utf8(&buf[gen_start..gen_end]).ok();
+        if let (Some(Ok(num)), Some(Ok(gen))) = (num.map(str::parse), gen.map(str::parse)) {
+            headers.push((object::Reference::new(num, gen), num_start));
+        }
+    }
+    return headers;
+}
+
+/// Finds the end of stream data starting at `start` by searching for the `endstream` keyword, the
+/// end-of-line marker preceding it not being part of the data
+fn scan_endstream(buf: &[u8], start: usize) -> Result<usize, PDFProcessingError> {
+    let keyword = b"endstream";
+    let position = buf[start..]
+        .windows(keyword.len())
+        .position(|w| w == keyword)
+        .ok_or_else(|| {
+            PDFProcessingError::EOF(format!(
+                "Missing 'endstream' for stream starting at offset {}",
+                start
+            ))
+        })?;
+
+    let data = &buf[start..start + position];
+    let eol = match data {
+        [.., b'\r', b'\n'] => 2,
+        [.., b'\n'] | [.., b'\r'] => 1,
+        _ => 0,
+    };
+    return Ok(start + position - eol);
+}
+
+/// Parses the lexeme of a numeric object (7.3.3), an optionally signed integer or real number
+/// such as `+17`, `-98`, `34.5`, `-.002` or `4.`
+fn parse_number(
+    lexeme: &[u8],
+    offset: usize,
+) -> Result<object::NumericObjectType, PDFProcessingError> {
+    let (negative, unsigned) = match lexeme {
+        [b'-', rest @ ..] => (true, rest),
+        [b'+', rest @ ..] => (false, rest),
+        _ => (false, lexeme),
+    };
+
+    let digits = unsigned.iter().filter(|b| b.is_ascii_digit()).count();
+    let points = unsigned.iter().filter(|&&b| b == b'.').count();
+    if digits == 0 || digits + points != unsigned.len() || points > 1 {
+        return Err(PDFProcessingError::Syntax(
+            offset,
+            format!(
+                "Invalid numeric object '{}'",
+                String::from_utf8_lossy(lexeme)
+            ),
+        ));
+    }
+
+    if points == 1 {
+        // The lexeme is validated ascii, which the standard library parses exactly
+        let real: f64 = std::str::from_utf8(lexeme)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|r: &f64| r.is_finite())
+            .ok_or_else(|| {
+                PDFProcessingError::Syntax(offset, "Real number exceeds an f64".to_string())
+            })?;
+        return Ok(object::NumericObjectType::Real(real));
+    }
+
+    // Accumulate towards the sign so i64::MIN can be represented
+    let mut integer: i64 = 0;
+    for &byte in unsigned {
+        let digit = (byte - b'0') as i64;
+        integer = integer
+            .checked_mul(10)
+            .and_then(|v| {
+                if negative {
+                    v.checked_sub(digit)
+                } else {
+                    v.checked_add(digit)
+                }
+            })
+            .ok_or_else(|| {
+                PDFProcessingError::Syntax(offset, "Integer exceeds an i64".to_string())
+            })?;
+    }
+    return Ok(object::NumericObjectType::Integer(integer));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use base64::{engine::general_purpose, Engine as _};
+
+    static TAIL_PDF_B64: &str = "vfNy9A5L0NttRG/9H3qLeuhNzqM3ioFebwZ6bcmvUQq96n70SuK/fF/0MuK15DT0YuK3sMQipHc+mTl3DfScJdCzl0XP0gI9o8ySbiJ66lfoKf6gJyuInlj8hkksvtxPnHHoMReiy3eP9vs72s9saF/noH2S/fsQ2tsaaKGlaF49NKcPmnkFTfuA9lL2z+ahPV6A9mA/2t0paLeGoF3finZlG9pFOTv3Ce1MXLST2dGOfkM7JDX756PJ74+2W3R2dkbbtg9t82e0jSXQ1g1HW3MZbVVqtOVd0ZZIr4U+2vx8aHNGos28ijY9DG2K9Jko2uNeoY0pgDayP9qwvWiDRW9QFbT+DdH69EbrORatm/jtrNA6JkZrXwatjZy1PIHWrBVaY9GrnwWtdmm0anPRqkpuZeHpIVGE+qUgNPSgvLcj1OcnoV5ZCXVvQqhzV0KRwtRthOVbliXUbAihxp8INRSOr7uIUK1BhKpLXdVIQpVXE6pwiFA5CJXaRqi45BapSKhgXkL56xDKE59QriSEcsg+azVCmZoTSi96aSQvxQNCyUoQSrycUNgSQvK3CsWWWPRvBH+EyX8eIPi2m+BzZYIPSwneCmuHhF29pwSOML15gkBbTyD/l4OnIwke9SW4P4/gzmSCm8L712S9LGcXZhGcFe4+Jc+JeARHMxEcEs7eL4y+5wnBbuH5HcLIW38SbLpFsH4swb/C2iu3ECyV+OIMBPNEd/ZKghmTCKYKk0+SHuOzEIwpQzDiBsHQhgT/5CMYkJCgbxWCXuKne3OCLgkIOtQjaJeLoHUYQQt5Gj8naFCVoK4wdq3CBNUmElTpTlBRuLvsToJSolFc8gqfJIgQ3XwbCP76QhB+myDrJYJMwvLynR+kkXtI+ZIg2UWCxOI9rD1B/IwEsUsQRL+M/ycK/4ew9tfm+B+P4b8rgv+6Cb43C9/+gW+cx381D/95TPzHcvYgFv5tE//GNfyrZ/EvyXpe2PxsBP7Jd/jHhX8Pa/gH7uLvk9yoNPg7L+Bvk7zNwsEbpMfaBfirH+Ov6Iu/RDQXnsGfdwt/dkn8GaI9VXh64jP8cePxR4/FH3Ebf+hq/H/G4feX2j6L8XvOwO82DL+z5Efa+G3347cSr80t/CYP8BuKn7qV8WuKTrXX+FVG41fsiF/WwS8lHF5sMH4hA79AHvy8/fD/Es3wjPjZwvAzvcVPJzWpx+CnWISfVHQSyWwJbuLHS4wfuwF+DLmvaNHxflXH+14B70slvI858N6lxXtdG8+Phec4eOZNPE3Y+cVcvKf/4j3aj3dP9neEWW98xbuWBe+y8PEF4fqzB/FOHcI7HoV3RBj6oHD8PnmPOoe36x3eDmHkrQ3xNs3AW38M79+reKvD8JZfwpN79BYOwpvXHW+28PKM/nhTK+JNGoo3/jLemFN4I4Xfh0uPweJp4FO8ftK39z68Hhfxukr/TsLwkdvw2hp4rU7iNZd+TaRHQ+HqesLptYXNa2TGq/obr3I4XoXSeGXFf6kkeMVl3iLyXrAGXv72eHkq4+V8iJd9JF6WwXgZ++KlS4OXSvg9qWj9bwteQpk3nrzHklnlW9X9kw/3ZyXcr8Lbn47jvu+O+1rh+sLbqgCuKeytTcZ9UR73iTDrwz24917g3j6Ge2Mz7tW7uJcu456X+JkWuCc74R4T/j/cBffAaty9wty7pe9O4eLtqXG3SN5G4e11SXBXC6uvTIW7LBPuotu4803cOadwZ/bAnZYId9IX3PFncMcMwB05EXdYK9zBYbgDwe0rbN0rO263arhyh27kUdy2MXFb6rjNRKvRDtz6Z3HriN+aS3GrebhV7uNWlLnKS16ZCNySuXGLFcQtdA63wEbcvIdx/1qCK98FbnZh+yxbcDNOw00nc6e+gZviIW5S8fS/WbgJRS/eDNzYj3FjPMGNtg71S9j4u7Dsl+2oj+9R7/+HepMG5Z9AKeFxqzpK74d6Kcz7DNRjiT0IQ90VTr8p7H/tJepKDtTFwqizb1CnAtRxYdkjS1EHb6D2PUNF7UDtWo3aLsy7RVh7Yy3Uukqo1T5qZX7UMtkvboxa0AYl/KRm1URNF60p2VATZR2XHjXqCWr4LtQQ4ehBJmpAEVTfLKhewv3dhfm7ZEB1TIVqnwTV+jmqxQtUU6lptBNV/yGqzi1Uzcuoav+gqjRCVRTWLzccVVpmLzEZVbQ4qmA+VD7Z/yX14a1Q2dqiMidApRfdNONRKbuhko1FJW6HCsuKivcTFScWKoZwfbQBOL9q43x7ifP5O87HVDjvuuKE5Nw9hmMK/2pTcZ4bOE8l9tDBuSfx28LIN4SVr+bCuZQR5zw4py2cE2twjgoHH3qBs184d88fnP/e4ey4i7O1C86mQTjrK+H8Wwtn1VycZRNwFg/GWSA8PTcrzsz8OFOEqSd8xRkzDWek8P4wqRksOQPr4/STnF6ROF2f4XTqjxPZB6dtEZyWl3CaVcVpJKxd7yJObfFRQ/z9XQOnsmiVX4RTRjRKjsIpJnWFm+FEdMfJVx4ndxhOuORnFb+ZluCkF09pquCkLIqTrB1OYpk34WeceOItlvSOLvz8R7j2Z23sr8KpH2djvxN+fd0G29+BrVZhW4ew9S7YL4XnnyXFfiTcfl+4/s4Z7JuNsa9VwL4kjH7exz6zDPtkGPZR4dyDH7D32dhRR7F3utjbFmILK9kblmCv3Yy9egP2CuH1pQWxFwn/zxcmniPcPDMu9rQy2JOF48efxh4jtaOEo4eLr8GiOVD89JuP3Vu4v0cN7C4Bdsdp2O0zYLcehd1cdJusx24oMeElu9Yj7OrnsKu2w654DbucMH3pztglxGtR0SmUFbtAIuy8wv5/NcTO8RU760/szFmw00uvtBJLBXYy0U0sNWEZseP9wI4THztmSmyEx383wPrRCutrBayPX7HeCcuGzmJ5h7Ec4VbzC5YmHP1SWPlZHqzHwrQP2mLdbYx1S9j1ujyXf2Kdn4F1+hvW8TdYR6XHwbtY+8tgRa3B2iU8v20W1uaiWBuKY639H9aq3VjLhcsXP8ZaIBw8V7h4pok1PTXWlFRYEyysseJntHD68HtYQ4SXB63D6t8Bq/d3rJ5gdRVvncVPZIDVLjlWqztYzd9iNYnEahgdq670rS0sXyM7VlXxVqk2VnmZs0x5rBIXsIpKfSHh9gI3sPJKn1wfsHKITtZHWJk+YqUX/TQHsFJI36QhrMQ1scJk7vhRWHFkrpjSjzmYv19g/tiL+bUX5qfqmO8rYL4R9g3iYCphdOsJppEE81V7zOfCu48DzAdPMe8J394Wxr4uPHzFxLxoYZ6T9UwizBPC18fSYx4WXj8grL9Xnv+EtXeIztbZmJuEldcJ56+Jwlwh6zKpWfQFc76POVdqZmXHnLYWc/IkzPHPMMdKfKQ8Q69j/iN6Ax5j9n2I2VvOutfB7Dwes0MNzHZS2+owZvOCmI2vYDaIj1lnP2ZN6VOtFGZlOatwELOssHjpMpglhNOL/MYsJD0KVMTMIzz+VwbM8GSYWWX2zBLPMB8z7T3MVH0xkyfFTJIcM+wCZvwbmHHGYMZsgInw6u+KGD/yYnz5gvEpHsb7HBhv5CzojeGux7DHYRhbMV6txXi+D+OJMPjDSxj3jmPcrolxXdj8yi6Mi6Uxzs3EOD0K44TkHAvHOCyMfmAOxt4LGLuFw3dGYWxPhLElDcbGJBjrMmCsEZ2VwsfLLIzFrzAW6Bhzb2DMuooxXfSmHMaY0AdjjOiPkPjQ+xiD42AMeIPRdxJGr54Y3RthdCmK0bEZRvsBGG0OYrQ8jdFsGUbjERgN2mHU7Y5RKxKjusSrZseouBej3BGM0hMwSvTHKCqsXlBmyv8OI49weq7fGDluY2QVz5nEYzrh9NRyRynEW7JMGIn7YoSdwkggM8QVf7FOYkR/gv5nAfqPcuhfXPSPA9HfLUOXbx09EK5VddDNVeivQujPy6M/kfjDc+j3E6HfEba9mRH96ib0S8LQF/Kgn52KfspCP5Eb/ahw7yGJHRAO3jsUfbeGvkt0tq9A3xoTfVNv9PUP0NemRV/dA33FYfSlBvriNOgLKqHPrYo+qw/6tKvok/eiTxCNsWfRR51BHy7vQz6jDxL+7i+5fVKh96iG3rUTeifh58jR6G1nordajN68GHrjHegNPPS6A9BrSX71LOhVVqJXFP/lmqKXFq8l+qEXFb+FJJZfeueRmf+KQA/PhJ4tO3rmCugZZJa0PdFTXURPbqMnTY3+P/GbUGLx5H5id0OPMQJdvnu0Xxraj/hoXwajfZT9O2HsNxFowRg0txia3QTNiEJ79QTtheyfDkV7JPx7fyXaHeH6m23RrgkDXxauviBnZ5+hnXqDdiIB2hHh7INSs28Ymvz+aP+Jzo7aaFtXoG16jrYhE9raSLTVu9FW/EFbVhdtsfRacAdtXnK02R3RZuxBm/oRbbL0mSDaYy+ijU6JNqI52tDlaP+I3sA8aP1KovVujNZD+L+r+O10Ey3yC1q7bGit5azFRrSmldAaiV69uGi1sqL9PQStivSvJDw9LNn/AcYiApcKZW5kc3RyZWFtCmVuZG9iagpzdGFydHhyZWYKMTU1NjMwMjcKJSVFT0YK";
+
+    #[test]
+    fn test_xref_offset() {
+        let tail_pdf = general_purpose::STANDARD
+            .decode(TAIL_PDF_B64)
+            .expect("Failed to decode b64 string");
+
+        let mut pdf = PDF::new();
+
+        let offset = pdf
+            .parse_xref_offset(&tail_pdf)
+            .expect("Failed to find xref offset");
+        assert_eq!(offset, 15563027)
+    }
+
+    #[test]
+    fn test_xref_offset_trailing_garbage() {
+        let mut pdf = PDF::new();
+        let mut buf = b"trailer\n<< >>\nstartxref\n1234\n%%EOF\n".to_vec();
+        buf.extend_from_slice(&[0u8; 600]);
+        assert_eq!(pdf.parse_xref_offset(&buf).unwrap(), 1234);
+
+        // Buffers shorter than the search window do not underflow
+        assert_eq!(pdf.parse_xref_offset(b"startxref 9").unwrap(), 9);
+        assert!(pdf.parse_xref_offset(b"%%EOF").is_err());
+        assert!(pdf.parse_xref_offset(b"").is_err());
+        assert!(pdf.parse_xref_offset(b"startxref").is_err());
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let mut pdf = PDF::new();
+        let (offset, version) = pdf
+            .parse_header(b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n")
+            .expect("Failed to parse header");
+        assert_eq!(offset, 0);
+        assert_eq!(version, Version::new(1, 7));
+        assert!(pdf.warnings.is_empty());
+
+        let (offset, version) = pdf
+            .parse_header(b"Content-Type: application/pdf\r\n\r\n%PDF-2.0\r\n")
+            .expect("Failed to parse header");
+        assert_eq!(offset, 33);
+        assert_eq!(version, Version::new(2, 0));
+        assert_eq!(pdf.warnings.len(), 1);
+
+        let mut junk = vec![b' '; 1024];
+        junk.extend_from_slice(b"%PDF-1.4\n");
+        assert!(pdf.parse_header(&junk).is_err());
+    }
+
+    #[test]
+    fn test_parse_literalstring() {
+        let mut pdf = PDF::new();
         let buf = b"(my example string)";
         let obj = pdf
             .parse_literalstring(buf, 0)
@@ -299,6 +1598,686 @@ mod tests {
         assert_eq!(obj.size(), 19)
     }
 
+    #[test]
+    fn test_parse_literalstring_nested() {
+        let mut pdf = PDF::new();
+        let buf = b"(a (nested) b \\) c) (next)";
+        let obj = pdf
+            .parse_literalstring(buf, 0)
+            .expect("Failed to parse literal string object");
+        assert_eq!(obj.string_bytes().unwrap(), &b"a (nested) b ) c"[..]);
+    }
+
+    #[test]
+    fn test_parse_hexstring() {
+        let mut pdf = PDF::new();
+        let buf = b"<48 65 6C 6C 6F 2>";
+        let obj = pdf
+            .parse_hexstring(buf, 0)
+            .expect("Failed to parse hex string object");
+        assert_eq!(obj.string_bytes().unwrap(), &b"Hello "[..]);
+        assert_eq!(obj.size(), buf.len());
+    }
+
+    #[test]
+    fn test_parse_nameobject() {
+        let mut pdf = PDF::new();
+        let buf = b"/Type /Lime#20Green";
+        let obj = pdf
+            .parse_nameobject(buf, 5)
+            .expect("Failed to parse name object");
+        let name = obj.as_name().expect("Expected a name object");
+        assert_eq!(name, "Lime Green");
+        assert_eq!(name.to_string(), "/Lime#20Green");
+    }
+
+    #[test]
+    fn test_parse_numericobject() {
+        let mut pdf = PDF::new();
+        let buf = b"+17 -98 0 34.5 -.002 4. -9223372036854775808";
+        let mut offset = 0;
+        let mut values = vec![];
+        while offset < buf.len() {
+            let obj = pdf
+                .parse_numericobject(buf, offset)
+                .expect("Failed to parse numeric object");
+            offset = pdf.lexer.getp();
+            values.push(obj.as_number().expect("Expected a numeric object"));
+        }
+        assert_eq!(
+            values,
+            vec![17.0, -98.0, 0.0, 34.5, -0.002, 4.0, i64::MIN as f64]
+        );
+    }
+
+    #[test]
+    fn test_parse_numericobject_errors() {
+        let mut pdf = PDF::new();
+        for buf in [&b"9223372036854775808"[..], b"1.2.3", b"-", b"12a"] {
+            assert!(matches!(
+                pdf.parse_numericobject(buf, 0),
+                Err(PDFProcessingError::Syntax(0, _))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_array() {
+        let mut pdf = PDF::new();
+        let buf = b"/MediaBox [0 0 612.5 792 [/Nested] (s) 12 0 R true null] %done";
+        let obj = pdf
+            .parse_object(buf, 9)
+            .expect("Failed to parse array object");
+        assert_eq!(pdf.lexer.getp(), 56);
+
+        let children = obj.as_array().expect("Expected an array");
+        assert_eq!(children.len(), 9);
+        assert_eq!(children[3].as_int(), Some(792));
+        assert_eq!(
+            children[4].to_owned(),
+            object::Object::array(vec![object::Object::name("Nested")])
+        );
+        assert_eq!(
+            children[6].as_reference(),
+            Some(object::Reference::new(12, 0))
+        );
+        assert!(children[8].is_null());
+    }
+
+    #[test]
+    fn test_parse_nesting_limit() {
+        let mut pdf = PDF::new();
+        let hostile = [b"<< /Root ".as_slice(), &[b'['; 200_000]].concat();
+        assert!(matches!(
+            pdf.parse_object(&hostile, 0),
+            Err(PDFProcessingError::Syntax(_, _))
+        ));
+        assert_eq!(pdf.nesting, 0);
+
+        let mut deepest = vec![b'['; MAX_NESTING];
+        deepest.extend(vec![b']'; MAX_NESTING]);
+        assert!(pdf.parse_object(&deepest, 0).is_ok());
+        let too_deep = [b"<< /A ".as_slice(), &deepest, b" >>"].concat();
+        assert!(pdf.parse_object(&too_deep, 0).is_err());
+        assert_eq!(pdf.nesting, 0);
+    }
+
+    #[test]
+    fn test_parse_dictionary() {
+        let mut pdf = PDF::new();
+        let buf = b"<</Type /Page/MediaBox[0 0 612 792]/Parent 3 0 R\n\
+            /Resources<</Font<<>>>> /Count 3 /Count 4>>";
+        let obj = pdf
+            .parse_object(buf, 0)
+            .expect("Failed to parse dictionary object");
+        assert_eq!(pdf.lexer.getp(), buf.len());
+
+        let dict = obj.as_dict().expect("Expected a dictionary");
+        assert_eq!(dict.len(), 5);
+        assert_eq!(dict.get_name("Type").unwrap(), "Page");
+        assert_eq!(dict.get_array("MediaBox").unwrap().len(), 4);
+        assert_eq!(dict.get_ref(b"Parent"), Some(object::Reference::new(3, 0)));
+        assert!(dict
+            .get_dict("Resources")
+            .unwrap()
+            .get_dict("Font")
+            .unwrap()
+            .is_empty());
+        assert_eq!(dict.get_int("Count"), Some(4));
+        assert_eq!(dict.get_int("Type"), None);
+        assert_eq!(dict.iter().last().unwrap().0, "Count");
+
+        assert_eq!(pdf.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_reference() {
+        let mut pdf = PDF::new();
+        let buf = b"12 0 R 7 0 1 R";
+
+        let obj = pdf.parse_object(buf, 0).expect("Failed to parse reference");
+        assert_eq!(obj.as_reference(), Some(object::Reference::new(12, 0)));
+        assert_eq!(obj.size(), 6);
+
+        // Only the last two integers before R form a reference
+        let obj = pdf.parse_object(buf, 6).expect("Failed to parse integer");
+        assert_eq!(obj.as_int(), Some(7));
+        let obj = pdf.parse_object(buf, pdf.lexer.getp()).unwrap();
+        assert_eq!(obj.as_reference(), Some(object::Reference::new(0, 1)));
+        assert_eq!(pdf.lexer.getp(), buf.len());
+    }
+
+    #[test]
+    fn test_parse_indirect_object() {
+        let mut pdf = PDF::new();
+        let buf = b"%PDF-1.7\n1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n2 0 obj 3 % no endobj\n";
+
+        let (reference, obj) = pdf
+            .parse_indirect_object(buf, 9, Some(object::Reference::new(1, 0)))
+            .expect("Failed to parse indirect object");
+        assert_eq!(reference, object::Reference::new(1, 0));
+        assert_eq!(obj.as_dict().unwrap().get_name("Type").unwrap(), "Catalog");
+        assert!(pdf.warnings.is_empty());
+
+        let next = pdf.lexer.getp();
+        assert!(pdf
+            .parse_indirect_object(buf, next, Some(object::Reference::new(3, 0)))
+            .is_err());
+
+        let (reference, obj) = pdf
+            .parse_indirect_object(buf, next, None)
+            .expect("Failed to parse indirect object without endobj");
+        assert_eq!(reference, object::Reference::new(2, 0));
+        assert_eq!(obj.as_int(), Some(3));
+        assert_eq!(pdf.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_stream() {
+        let mut pdf = PDF::new();
+        let buf = b"4 0 obj\n<< /Length 10 >>\nstream\r\n0123456789\r\nendstream\nendobj\n";
+        let (_, obj) = pdf
+            .parse_indirect_object(buf, 0, Some(object::Reference::new(4, 0)))
+            .expect("Failed to parse stream object");
+        assert_eq!(obj.as_stream().unwrap().data, b"0123456789");
+        assert_eq!(pdf.lexer.getp(), buf.len() - 1);
+        assert!(pdf.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_parse_stream_wrong_length() {
+        for length in ["3", "100", "-1", "(x)"] {
+            let mut pdf = PDF::new();
+            let buf = format!(
+                "4 0 obj\n<< /Length {} >>\nstream\n0123456789\nendstream\nendobj\n",
+                length
+            );
+            let (_, obj) = pdf
+                .parse_indirect_object(buf.as_bytes(), 0, None)
+                .expect("Failed to parse stream object");
+            assert_eq!(obj.as_stream().unwrap().data, b"0123456789");
+            assert_eq!(pdf.warnings.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_parse_stream_indirect_length() {
+        let mut pdf = PDF::new();
+        let buf = b"4 0 obj\n<< /Length 5 0 R >>\nstream\nendstream data\nendstream\nendobj\n\
+            5 0 obj 14 endobj\n";
+        let length_offset = buf.len() - 18;
+        pdf.xref.insert(
+            5,
+            xref::XrefEntry::InUse {
+                offset: length_offset,
+                gen: 0,
+            },
+        );
+
+        let (_, obj) = pdf
+            .parse_indirect_object(buf, 0, None)
+            .expect("Failed to parse stream object");
+        assert_eq!(obj.as_stream().unwrap().data, b"endstream data");
+        assert!(pdf.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_compressed_object() {
+        let content = b"10 0 11 11 << /A 1 >> [7 8]";
+        let mut buf = format!(
+            "5 0 obj\n<< /Type /ObjStm /N 2 /First 11 /Length {} >>\nstream\n",
+            content.len()
+        )
+        .into_bytes();
+        buf.extend_from_slice(content);
+        buf.extend_from_slice(b"\nendstream\nendobj\n");
+
+        let mut pdf = PDF::new();
+        pdf.xref
+            .insert(5, xref::XrefEntry::InUse { offset: 0, gen: 0 });
+        pdf.xref.insert(
+            10,
+            xref::XrefEntry::Compressed {
+                stream: 5,
+                index: 0,
+            },
+        );
+        pdf.xref.insert(
+            11,
+            xref::XrefEntry::Compressed {
+                stream: 5,
+                index: 1,
+            },
+        );
+        pdf.xref.insert(
+            12,
+            xref::XrefEntry::Compressed {
+                stream: 5,
+                index: 1,
+            },
+        );
+
+        let dict = pdf
+            .resolve(&buf, object::Reference::new(10, 0))
+            .expect("Failed to resolve compressed object");
+        assert_eq!(
+            dict,
+            object::Object::dictionary(object::Dictionary::new().with("A", 1))
+        );
+        let array = pdf
+            .resolve(&buf, object::Reference::new(11, 0))
+            .expect("Failed to resolve compressed object");
+        assert_eq!(
+            array,
+            object::Object::array(vec![object::Object::integer(7), object::Object::integer(8)])
+        );
+        assert_eq!(pdf.object_streams.len(), 1);
+
+        // Decoded objects have no borrowed form
+        assert!(pdf
+            .resolve_ref(&buf, object::Reference::new(10, 0))
+            .is_err());
+        // The object stream holds object 11 rather than 12 at that index
+        assert!(pdf.resolve(&buf, object::Reference::new(12, 0)).is_err());
+        // Object numbers missing from the xref table refer to the null object
+        assert!(pdf
+            .resolve(&buf, object::Reference::new(13, 0))
+            .unwrap()
+            .is_null());
+        assert!(pdf.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_structural_streams_reject_image_codecs() {
+        let mut pdf = PDF::new();
+        let buf = b"5 0 obj\n<< /Type /ObjStm /N 1 /First 4 /Filter /DCTDecode /Length 9 >>\n\
+            stream\n10 0 true\nendstream\nendobj\n";
+        pdf.xref
+            .insert(5, xref::XrefEntry::InUse { offset: 0, gen: 0 });
+        pdf.xref.insert(
+            10,
+            xref::XrefEntry::Compressed {
+                stream: 5,
+                index: 0,
+            },
+        );
+        assert!(pdf.resolve(buf, object::Reference::new(10, 0)).is_err());
+
+        let mut pdf = PDF::new();
+        let buf = b"5 0 obj\n<< /Type /XRef /Size 1 /W [1 1 1] /Filter [/AHx /JBIG2Decode] \
+            /Length 7 >>\nstream\n011100>\nendstream\nendobj\n";
+        assert!(pdf.parse_xref(buf, 0).is_err());
+    }
+
+    #[test]
+    fn test_shifted_offsets_do_not_overflow() {
+        let buf = b"junk\n%PDF-1.7\n1 0 obj\nnull\nendobj\n";
+        let mut pdf = PDF::new();
+        pdf.offset_shift = 5;
+        pdf.xref.insert(
+            1,
+            xref::XrefEntry::InUse {
+                offset: usize::MAX - 1,
+                gen: 0,
+            },
+        );
+        pdf.xref.insert(
+            2,
+            xref::XrefEntry::Compressed {
+                stream: 1,
+                index: 0,
+            },
+        );
+        assert!(pdf.resolve(buf, object::Reference::new(1, 0)).is_err());
+        assert!(pdf.resolve(buf, object::Reference::new(2, 0)).is_err());
+        assert!(pdf.parse_xref(buf, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_resolve_stale_generation() {
+        let buf = b"4 1 obj\n(current)\nendobj\n";
+        let mut pdf = PDF::new();
+        pdf.xref
+            .insert(4, xref::XrefEntry::InUse { offset: 0, gen: 1 });
+
+        assert_eq!(
+            pdf.resolve(buf, object::Reference::new(4, 1)).unwrap(),
+            object::Object::string(b"current".to_vec())
+        );
+        assert!(pdf.warnings.is_empty());
+        assert!(pdf
+            .resolve(buf, object::Reference::new(4, 0))
+            .unwrap()
+            .is_null());
+        assert_eq!(pdf.warnings.len(), 1);
+        assert!(pdf.resolve(buf, object::Reference::new(5, 0)).is_ok());
+
+        pdf.xref
+            .insert(5, xref::XrefEntry::InUse { offset: 0, gen: 0 });
+        assert!(pdf.resolve(buf, object::Reference::new(5, 0)).is_err());
+    }
+
+    #[test]
+    fn test_parse_xref_table() {
+        let mut pdf = PDF::new();
+        let buf = b"xref\r\n0 3\r\n0000000000 65535 f\r\n0000000017 00000 n\r\n\
+            0000000081 00000 n\r\n7 1\r\n0000000402 00002 n\r\ntrailer\r\n<< >>";
+        let table = pdf
+            .parse_xref_table(buf, 0)
+            .expect("Failed to parse xref table");
+
+        assert_eq!(table.len(), 4);
+        assert_eq!(
+            table.get(0),
+            Some(&xref::XrefEntry::Free {
+                next: 0,
+                gen: 65535
+            })
+        );
+        assert_eq!(
+            table.get(2),
+            Some(&xref::XrefEntry::InUse { offset: 81, gen: 0 })
+        );
+        assert_eq!(
+            table.get(7),
+            Some(&xref::XrefEntry::InUse {
+                offset: 402,
+                gen: 2
+            })
+        );
+        assert!(pdf.warnings.is_empty());
+
+        let tok = pdf.lexer.next(buf).unwrap();
+        assert_eq!(tok.typ, lexer::TokenType::Keyword(KeywordType::Trailer));
+    }
+
+    #[test]
+    fn test_parse_xref_table_malformed() {
+        // Single byte end-of-line markers and a missing subsection header
+        let mut pdf = PDF::new();
+        let buf = b"xref\n0000000000 65535 f\n0000000017 00000 n\ntrailer";
+        let table = pdf
+            .parse_xref_table(buf, 0)
+            .expect("Failed to parse xref table");
+        assert_eq!(
+            table.get(1),
+            Some(&xref::XrefEntry::InUse { offset: 17, gen: 0 })
+        );
+        assert_eq!(pdf.warnings.len(), 1);
+
+        // Off-by-one first object number
+        let mut pdf = PDF::new();
+        let buf =
+            b"xref\n1 3\n0000000000 65535 f \n0000000017 00000 n \n0000000081 00000 n \ntrailer";
+        let table = pdf
+            .parse_xref_table(buf, 0)
+            .expect("Failed to parse xref table");
+        assert_eq!(table.len(), 3);
+        assert_eq!(
+            table.get(2),
+            Some(&xref::XrefEntry::InUse { offset: 81, gen: 0 })
+        );
+        assert_eq!(table.get(3), None);
+        assert_eq!(pdf.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_xref_stream() {
+        let mut pdf = PDF::new();
+        #[rustfmt::skip]
+        let rows: &[u8] = &[
+            0, 0x00, 0x00, 0xFF,
+            1, 0x00, 0x11, 0x00,
+            2, 0x00, 0x0A, 0x03,
+            1, 0x01, 0x00, 0x02,
+        ];
+        let mut buf = b"%junk\n12 0 obj\n<< /Type /XRef /Size 12 /W [1 2 1] /Index [0 3 11 1] \
+            /Length 16 >>\nstream\n"
+            .to_vec();
+        buf.extend_from_slice(rows);
+        buf.extend_from_slice(b"\nendstream\nendobj\n");
+
+        let (table, trailer) = pdf
+            .parse_xref(&buf, 6)
+            .expect("Failed to parse xref stream");
+        assert_eq!(trailer.get_int("Size"), Some(12));
+        assert_eq!(table.len(), 4);
+        assert_eq!(
+            table.get(0),
+            Some(&xref::XrefEntry::Free { next: 0, gen: 255 })
+        );
+        assert_eq!(
+            table.get(1),
+            Some(&xref::XrefEntry::InUse { offset: 17, gen: 0 })
+        );
+        assert_eq!(
+            table.get(2),
+            Some(&xref::XrefEntry::Compressed {
+                stream: 10,
+                index: 3
+            })
+        );
+        assert_eq!(
+            table.get(11),
+            Some(&xref::XrefEntry::InUse {
+                offset: 256,
+                gen: 2
+            })
+        );
+        assert!(pdf.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_parse_xref_stream_out_of_range() {
+        #[rustfmt::skip]
+        let rows: &[u8] = &[
+            1, 0x00, 0x00, 0x00, 0x00, 0x11, 0x00, 0x00, 0x00,
+            0, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // next overflows u32
+            1, 0x00, 0x00, 0x00, 0x00, 0x22, 0x01, 0x00, 0x00, // gen overflows u16
+            2, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // stream overflows u32
+        ];
+        let mut buf =
+            b"5 0 obj\n<< /Type /XRef /Size 4 /W [1 5 3] /Length 36 >>\nstream\n".to_vec();
+        buf.extend_from_slice(rows);
+        buf.extend_from_slice(b"\nendstream\nendobj\n");
+
+        let mut pdf = PDF::new();
+        let (table, _) = pdf
+            .parse_xref(&buf, 0)
+            .expect("Failed to parse xref stream");
+        assert_eq!(table.len(), 1);
+        assert_eq!(
+            table.get(0),
+            Some(&xref::XrefEntry::InUse { offset: 17, gen: 0 })
+        );
+        assert_eq!(pdf.warnings.len(), 3);
+
+        let mut pdf = PDF::new();
+        let buf = b"5 0 obj\n<< /Type /XRef /Size 1 /W [1 9 1] /Length 11 >>\nstream\n\
+            \x01\0\0\0\0\0\0\0\0\x11\0\nendstream\nendobj\n";
+        assert!(pdf.parse_xref(buf, 0).is_err());
+    }
+
+    #[test]
+    fn test_parse_xref_stream_predictor() {
+        // Rows of /W [1 3 1], each PNG Up predicted from the previous row
+        let rows: [[u8; 5]; 3] = [[0, 0, 0, 0, 255], [1, 0, 0, 15, 0], [2, 0, 0, 7, 0]];
+        let mut predicted = vec![];
+        let mut prev = [0u8; 5];
+        for row in rows {
+            predicted.push(2);
+            predicted.extend(row.iter().zip(prev).map(|(b, p)| b.wrapping_sub(p)));
+            prev = row;
+        }
+        let data = miniz_oxide::deflate::compress_to_vec_zlib(&predicted, 6);
+
+        let mut buf = format!(
+            "9 0 obj\n<< /Type /XRef /Size 3 /W [1 3 1] /Filter /FlateDecode \
+            /DecodeParms << /Columns 5 /Predictor 12 >> /Length {} >>\nstream\n",
+            data.len()
+        )
+        .into_bytes();
+        buf.extend_from_slice(&data);
+        buf.extend_from_slice(b"\nendstream\nendobj\n");
+
+        let mut pdf = PDF::new();
+        let (table, _) = pdf
+            .parse_xref(&buf, 0)
+            .expect("Failed to parse xref stream");
+        assert_eq!(
+            table.get(1),
+            Some(&xref::XrefEntry::InUse { offset: 15, gen: 0 })
+        );
+        assert_eq!(
+            table.get(2),
+            Some(&xref::XrefEntry::Compressed {
+                stream: 7,
+                index: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_xref_prev_chain() {
+        let original = b"xref\n0 3\n0000000000 65535 f \n0000000017 00000 n \n\
+            0000000081 00000 n \ntrailer\n<< /Size 3 /Root 1 0 R >>\n";
+        let update =
+            b"xref\n0 1\n0000000000 65535 f \n2 2\n0000000300 00001 n \n0000000400 00000 n \n\
+            trailer\n<< /Size 4 /Root 1 0 R /Prev 0 >>\n";
+        let buf = [&original[..], &update[..]].concat();
+
+        let mut pdf = PDF::new();
+        let (table, trailer) = pdf
+            .parse_xref(&buf, original.len())
+            .expect("Failed to parse xref chain");
+        assert_eq!(table.len(), 4);
+        assert_eq!(
+            table.get(1),
+            Some(&xref::XrefEntry::InUse { offset: 17, gen: 0 })
+        );
+        assert_eq!(
+            table.get(2),
+            Some(&xref::XrefEntry::InUse {
+                offset: 300,
+                gen: 1
+            })
+        );
+        assert_eq!(trailer.get_int("Size"), Some(4));
+        assert_eq!(trailer.get_ref("Root"), Some(object::Reference::new(1, 0)));
+        assert!(pdf.warnings.is_empty());
+
+        // A section whose /Prev points back at itself
+        let buf = b"xref\n0 1\n0000000000 65535 f \ntrailer\n<< /Size 1 /Prev 0 >>";
+        let mut pdf = PDF::new();
+        let (table, _) = pdf.parse_xref(buf, 0).expect("Failed to parse xref loop");
+        assert_eq!(table.len(), 1);
+        assert_eq!(pdf.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_xref_hybrid() {
+        #[rustfmt::skip]
+        let rows: &[u8] = &[
+            1, 0x00, 0x63, 0x00,
+            2, 0x00, 0x05, 0x00,
+            2, 0x00, 0x05, 0x01,
+            1, 0x00, 0x40, 0x00,
+        ];
+        let mut buf =
+            b"6 0 obj\n<< /Type /XRef /Size 7 /W [1 2 1] /Index [1 3 5 1] /Length 16 >>\n\
+            stream\n"
+                .to_vec();
+        buf.extend_from_slice(rows);
+        buf.extend_from_slice(b"\nendstream\nendobj\n");
+        let xref_offset = buf.len();
+        buf.extend_from_slice(
+            b"xref\n0 3\n0000000000 65535 f \n0000000017 00000 n \n0000000000 00000 f \n\
+            trailer\n<< /Size 7 /XRefStm 0 >>\n",
+        );
+
+        let mut pdf = PDF::new();
+        let (table, _) = pdf
+            .parse_xref(&buf, xref_offset)
+            .expect("Failed to parse hybrid xref");
+        assert_eq!(table.len(), 5);
+        // The table's in use entry wins over the stream's
+        assert_eq!(
+            table.get(1),
+            Some(&xref::XrefEntry::InUse { offset: 17, gen: 0 })
+        );
+        // The stream's entry replaces the table's free entry
+        assert_eq!(
+            table.get(2),
+            Some(&xref::XrefEntry::Compressed {
+                stream: 5,
+                index: 0
+            })
+        );
+        assert_eq!(
+            table.get(3),
+            Some(&xref::XrefEntry::Compressed {
+                stream: 5,
+                index: 1
+            })
+        );
+        assert_eq!(
+            table.get(5),
+            Some(&xref::XrefEntry::InUse { offset: 64, gen: 0 })
+        );
+        assert!(pdf.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_reconstruct_xref() {
+        let content = b"3 0 << /Type /Catalog >>";
+        let mut buf = b"%PDF-1.7\n1 0 obj\n(a 9 0 obj)\nendobj\n".to_vec();
+        let stream_offset = buf.len();
+        buf.extend_from_slice(
+            format!(
+                "2 0 obj\n<< /Type /ObjStm /N 1 /First 4 /Length {} >>\nstream\n",
+                content.len()
+            )
+            .as_bytes(),
+        );
+        buf.extend_from_slice(content);
+        buf.extend_from_slice(b"\nendstream\nendobj\n1 1 obj\n(updated)\nendobj\n");
+        let updated_offset = buf.len() - 25;
+        buf.extend_from_slice(b"trailer\n<< /Size 2 /Info 1 1 R /Prev 9 >>\n%%EOF");
+
+        let mut pdf = PDF::new();
+        let (table, trailer) = pdf
+            .reconstruct_xref(&buf)
+            .expect("Failed to reconstruct xref");
+        assert_eq!(table.len(), 3);
+        assert_eq!(
+            table.get(1),
+            Some(&xref::XrefEntry::InUse {
+                offset: updated_offset,
+                gen: 1
+            })
+        );
+        assert_eq!(
+            table.get(2),
+            Some(&xref::XrefEntry::InUse {
+                offset: stream_offset,
+                gen: 0
+            })
+        );
+        assert_eq!(
+            table.get(3),
+            Some(&xref::XrefEntry::Compressed {
+                stream: 2,
+                index: 0
+            })
+        );
+        assert_eq!(table.get(9), None);
+
+        assert_eq!(trailer.get_ref("Root"), Some(object::Reference::new(3, 0)));
+        assert_eq!(trailer.get_ref("Info"), Some(object::Reference::new(1, 1)));
+        assert_eq!(trailer.get_int("Size"), Some(4));
+        assert_eq!(trailer.get("Prev"), None);
+    }
+
     #[test]
     fn test_parse_xref() {
         // let tail_pdf = general_purpose::STANDARD
@@ -312,11 +2291,10 @@ mod tests {
         let content = fs::read(file_path).expect("Failed to read file");
         let slice = content.as_slice();
 
-        let lxr = lexer::Lexer::new();
-        let mut pdf = PDF { lexer: lxr };
+        let mut pdf = PDF::new();
 
-        let xref_offset = pdf
-            .parse_xref_offset(&slice)
+        let _xref_offset = pdf
+            .parse_xref_offset(slice)
             .expect("Failed to find xref offset");
 
         // let tokens = pdf
@@ -0,0 +1,318 @@
+use std::borrow::Cow;
+
+use super::{
+    Dictionary, Name, NumericObjectType, Object, ObjectType, Reference, Stream, StringObjectType,
+};
+use crate::filter;
+use crate::lexer::{self, PDFProcessingError};
+
+/// The zero-copy counterpart of [`Object`], borrowing from the buffer it was read from.
+///
+/// Strings, names and stream data are kept as the bytes that appear in the buffer and are only
+/// decoded when asked for. [`ObjectRef::to_owned`] copies the object out of the buffer.
+#[derive(Debug, Clone)]
+pub struct ObjectRef<'a> {
+    typ: ObjectRefType<'a>,
+    offset: usize,
+    size: usize,
+}
+
+impl<'a> ObjectRef<'a> {
+    pub fn new(typ: ObjectRefType<'a>, offset: usize, size: usize) -> ObjectRef<'a> {
+        return ObjectRef { typ, offset, size };
+    }
+
+    pub fn typ(&self) -> &ObjectRefType<'a> {
+        return &self.typ;
+    }
+
+    pub fn into_typ(self) -> ObjectRefType<'a> {
+        return self.typ;
+    }
+
+    /// Byte offset of the object in the buffer it was read from
+    pub fn offset(&self) -> usize {
+        return self.offset;
+    }
+
+    /// Number of bytes the object spans in the buffer it was read from
+    pub fn size(&self) -> usize {
+        return self.size;
+    }
+
+    pub fn is_null(&self) -> bool {
+        return self.typ == ObjectRefType::Null;
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        return match self.typ {
+            ObjectRefType::Boolean(value) => Some(value),
+            _ => None,
+        };
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        return match self.typ {
+            ObjectRefType::Numeric(NumericObjectType::Integer(value)) => Some(value),
+            _ => None,
+        };
+    }
+
+    /// See [`Object::as_number`]
+    pub fn as_number(&self) -> Option<f64> {
+        return match self.typ {
+            ObjectRefType::Numeric(NumericObjectType::Integer(value)) => Some(value as f64),
+            ObjectRefType::Numeric(NumericObjectType::Real(value)) => Some(value),
+            _ => None,
+        };
+    }
+
+    /// The decoded bytes of a string object. Literal strings without escape sequences or
+    /// end-of-line markers are borrowed.
+    pub fn string_bytes(&self) -> Option<Cow<'a, [u8]>> {
+        return match self.typ {
+            ObjectRefType::String(typ, lexeme) => Some(decode_string(typ, lexeme)),
+            _ => None,
+        };
+    }
+
+    /// The decoded name, borrowed unless it contains `#xx` escapes
+    pub fn as_name(&self) -> Option<Name<'a>> {
+        return match self.typ {
+            ObjectRefType::Name(lexeme) => Some(Name::from_lexeme(lexeme)),
+            _ => None,
+        };
+    }
+
+    pub fn as_array(&self) -> Option<&[ObjectRef<'a>]> {
+        return match &self.typ {
+            ObjectRefType::Array(children) => Some(children),
+            _ => None,
+        };
+    }
+
+    pub fn as_dict(&self) -> Option<&DictionaryRef<'a>> {
+        return match &self.typ {
+            ObjectRefType::Dictionary(dict) => Some(dict),
+            ObjectRefType::Stream(stream) => Some(&stream.dict),
+            _ => None,
+        };
+    }
+
+    pub fn as_stream(&self) -> Option<&StreamRef<'a>> {
+        return match &self.typ {
+            ObjectRefType::Stream(stream) => Some(stream),
+            _ => None,
+        };
+    }
+
+    pub fn as_reference(&self) -> Option<Reference> {
+        return match self.typ {
+            ObjectRefType::Reference(reference) => Some(reference),
+            _ => None,
+        };
+    }
+
+    /// Decodes and copies the object out of the buffer it was read from
+    pub fn to_owned(&self) -> Object {
+        let typ = match &self.typ {
+            ObjectRefType::Boolean(value) => ObjectType::Boolean(*value),
+            ObjectRefType::Numeric(value) => ObjectType::Numeric(*value),
+            ObjectRefType::String(typ, lexeme) => {
+                ObjectType::String(*typ, decode_string(*typ, lexeme).into_owned())
+            }
+            ObjectRefType::Name(lexeme) => ObjectType::Name(Name::from_lexeme(lexeme).into_owned()),
+            ObjectRefType::Array(children) => {
+                ObjectType::Array(children.iter().map(ObjectRef::to_owned).collect())
+            }
+            ObjectRefType::Dictionary(dict) => ObjectType::Dictionary(dict.to_owned()),
+            ObjectRefType::Stream(stream) => ObjectType::Stream(stream.to_owned()),
+            ObjectRefType::Null => ObjectType::Null,
+            ObjectRefType::Reference(reference) => ObjectType::Reference(*reference),
+        };
+        return Object::new(typ, self.offset, self.size);
+    }
+}
+
+impl PartialEq for ObjectRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        return self.typ == other.typ;
+    }
+}
+
+fn decode_string(typ: StringObjectType, lexeme: &[u8]) -> Cow<'_, [u8]> {
+    return match (typ, lexeme) {
+        (StringObjectType::Literal, [b'(', inner @ .., b')'])
+            if !inner.iter().any(|b| matches!(b, b'\\' | b'\r')) =>
+        {
+            Cow::Borrowed(inner)
+        }
+        (StringObjectType::Literal, _) => Cow::Owned(lexer::decode_literal_string(lexeme)),
+        (StringObjectType::Hexadecimal, _) => Cow::Owned(lexer::decode_hex_string(lexeme)),
+    };
+}
+
+/// The borrowed counterpart of [`Dictionary`]
+#[derive(Debug, Clone, Default)]
+pub struct DictionaryRef<'a> {
+    entries: Vec<(Name<'a>, ObjectRef<'a>)>,
+}
+
+impl<'a> DictionaryRef<'a> {
+    pub fn new() -> DictionaryRef<'a> {
+        return DictionaryRef { entries: vec![] };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    /// See [`Dictionary::insert`]
+    pub fn insert(&mut self, key: Name<'a>, value: ObjectRef<'a>) -> Option<ObjectRef<'a>> {
+        if let Some((_, existing)) = self.entries.iter_mut().find(|(k, _)| *k == key) {
+            return Some(std::mem::replace(existing, value));
+        }
+        self.entries.push((key, value));
+        return None;
+    }
+
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<&ObjectRef<'a>> {
+        let key = key.as_ref();
+        return self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Name<'a>, &ObjectRef<'a>)> {
+        return self.entries.iter().map(|(k, v)| (k, v));
+    }
+
+    pub fn get_name(&self, key: impl AsRef<[u8]>) -> Option<Name<'a>> {
+        return self.get(key)?.as_name();
+    }
+
+    pub fn get_int(&self, key: impl AsRef<[u8]>) -> Option<i64> {
+        return self.get(key)?.as_int();
+    }
+
+    pub fn get_array(&self, key: impl AsRef<[u8]>) -> Option<&[ObjectRef<'a>]> {
+        return self.get(key)?.as_array();
+    }
+
+    pub fn get_dict(&self, key: impl AsRef<[u8]>) -> Option<&DictionaryRef<'a>> {
+        return self.get(key)?.as_dict();
+    }
+
+    pub fn get_ref(&self, key: impl AsRef<[u8]>) -> Option<Reference> {
+        return self.get(key)?.as_reference();
+    }
+
+    pub fn to_owned(&self) -> Dictionary {
+        return self
+            .entries
+            .iter()
+            .map(|(k, v)| (k.clone().into_owned(), v.to_owned()))
+            .collect();
+    }
+}
+
+impl PartialEq for DictionaryRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        return self.len() == other.len()
+            && self.iter().all(|(k, v)| other.get(k.as_bytes()) == Some(v));
+    }
+}
+
+/// The borrowed counterpart of [`Stream`], `data` being a slice of the buffer
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamRef<'a> {
+    pub dict: DictionaryRef<'a>,
+    pub data: &'a [u8],
+}
+
+impl<'a> StreamRef<'a> {
+    /// The stream data with its filters applied, borrowed when the stream has no filters. Image
+    /// data is left encoded with its image codec.
+    pub fn decoded_data(&self) -> Result<Cow<'a, [u8]>, PDFProcessingError> {
+        return Ok(self.decode()?.data);
+    }
+
+    /// See [`Stream::image_params`]
+    pub fn image_params(&self) -> Option<filter::ImageParams> {
+        if self.dict.get_name("Subtype")? != "Image" {
+            return None;
+        }
+        return Some(filter::ImageParams::from_dict(&self.dict.to_owned()));
+    }
+
+    /// See [`Stream::decode`]
+    pub fn decode(&self) -> Result<filter::Decoded<'a>, PDFProcessingError> {
+        let filter = self.dict.get("Filter").map(ObjectRef::to_owned);
+        let params = self.dict.get("DecodeParms").map(ObjectRef::to_owned);
+        return filter::decode(self.data, filter.as_ref(), params.as_ref());
+    }
+
+    pub fn to_owned(&self) -> Stream {
+        return Stream::new(self.dict.to_owned(), self.data.to_vec());
+    }
+}
+
+/// The borrowed counterpart of [`ObjectType`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectRefType<'a> {
+    Boolean(bool),
+    Numeric(NumericObjectType),
+    /// The string's syntax and its lexeme, delimiters included
+    String(StringObjectType, &'a [u8]),
+    /// The name's lexeme, solidus included
+    Name(&'a [u8]),
+    Array(Vec<ObjectRef<'a>>),
+    Dictionary(DictionaryRef<'a>),
+    Stream(StreamRef<'a>),
+    Null,
+    Reference(Reference),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_lazily_and_converts_to_owned() {
+        let buf = b"(plain) (esc\\051) /A#20B";
+        let plain = ObjectRef::new(
+            ObjectRefType::String(StringObjectType::Literal, &buf[0..7]),
+            0,
+            7,
+        );
+        let escaped = ObjectRef::new(
+            ObjectRefType::String(StringObjectType::Literal, &buf[8..17]),
+            8,
+            9,
+        );
+        let name = ObjectRef::new(ObjectRefType::Name(&buf[18..]), 18, 7);
+
+        assert!(matches!(
+            plain.string_bytes(),
+            Some(Cow::Borrowed(b"plain"))
+        ));
+        assert_eq!(escaped.string_bytes().unwrap(), &b"esc)"[..]);
+        assert_eq!(name.as_name().unwrap(), "A B");
+
+        let mut dict = DictionaryRef::new();
+        dict.insert(Name::from("Str"), escaped);
+        dict.insert(Name::from("Name"), name);
+        let owned = ObjectRef::new(ObjectRefType::Dictionary(dict), 0, buf.len()).to_owned();
+        assert_eq!(
+            owned,
+            Object::dictionary(
+                Dictionary::new()
+                    .with("Str", Object::string(b"esc)".to_vec()))
+                    .with("Name", Object::name("A B"))
+            )
+        );
+        assert_eq!(owned.size(), buf.len());
+    }
+}
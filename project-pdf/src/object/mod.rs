@@ -1,3 +1,17 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::filter;
+use crate::lexer::PDFProcessingError;
+
+mod borrowed;
+pub use borrowed::{DictionaryRef, ObjectRef, ObjectRefType, StreamRef};
+
+/// A PDF object (7.3) together with the location it was read from.
+///
+/// Objects compare by value only, the offset and size are kept for diagnostics. Objects built
+/// programmatically have an offset and size of 0.
+#[derive(Debug, Clone)]
 pub struct Object {
     typ: ObjectType,
     offset: usize,
@@ -9,38 +23,514 @@ impl Object {
         return Object { typ, offset, size };
     }
 
-    pub fn typ(self) -> ObjectType {
+    pub fn typ(&self) -> &ObjectType {
+        return &self.typ;
+    }
+
+    pub fn typ_mut(&mut self) -> &mut ObjectType {
+        return &mut self.typ;
+    }
+
+    pub fn into_typ(self) -> ObjectType {
         return self.typ;
     }
 
-    pub fn offset(self) -> usize {
+    /// Byte offset of the object in the buffer it was read from
+    pub fn offset(&self) -> usize {
         return self.offset;
     }
 
-    pub fn size(self) -> usize {
+    /// Number of bytes the object spans in the buffer it was read from
+    pub fn size(&self) -> usize {
         return self.size;
     }
+
+    pub fn boolean(value: bool) -> Object {
+        return ObjectType::Boolean(value).into();
+    }
+
+    pub fn integer(value: i64) -> Object {
+        return ObjectType::Numeric(NumericObjectType::Integer(value)).into();
+    }
+
+    pub fn real(value: f64) -> Object {
+        return ObjectType::Numeric(NumericObjectType::Real(value)).into();
+    }
+
+    /// A string that will be written as a literal string
+    pub fn string(bytes: impl Into<Vec<u8>>) -> Object {
+        return ObjectType::String(StringObjectType::Literal, bytes.into()).into();
+    }
+
+    /// A string that will be written as a hexadecimal string
+    pub fn hex_string(bytes: impl Into<Vec<u8>>) -> Object {
+        return ObjectType::String(StringObjectType::Hexadecimal, bytes.into()).into();
+    }
+
+    pub fn name(name: impl Into<Name<'static>>) -> Object {
+        return ObjectType::Name(name.into()).into();
+    }
+
+    pub fn array(children: Vec<Object>) -> Object {
+        return ObjectType::Array(children).into();
+    }
+
+    pub fn dictionary(dict: Dictionary) -> Object {
+        return ObjectType::Dictionary(dict).into();
+    }
+
+    pub fn stream(dict: Dictionary, data: Vec<u8>) -> Object {
+        return ObjectType::Stream(Stream::new(dict, data)).into();
+    }
+
+    pub fn null() -> Object {
+        return ObjectType::Null.into();
+    }
+
+    pub fn reference(num: u32, gen: u16) -> Object {
+        return ObjectType::Reference(Reference::new(num, gen)).into();
+    }
+
+    pub fn is_null(&self) -> bool {
+        return self.typ == ObjectType::Null;
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        return match self.typ {
+            ObjectType::Boolean(value) => Some(value),
+            _ => None,
+        };
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        return match self.typ {
+            ObjectType::Numeric(NumericObjectType::Integer(value)) => Some(value),
+            _ => None,
+        };
+    }
+
+    /// The value of an integer or real object, integers being usable wherever a real is expected
+    /// (7.3.3)
+    pub fn as_number(&self) -> Option<f64> {
+        return match self.typ {
+            ObjectType::Numeric(NumericObjectType::Integer(value)) => Some(value as f64),
+            ObjectType::Numeric(NumericObjectType::Real(value)) => Some(value),
+            _ => None,
+        };
+    }
+
+    /// The decoded bytes of a string object, regardless of whether it was written as a literal or
+    /// hexadecimal string
+    pub fn string_bytes(&self) -> Option<&[u8]> {
+        return match &self.typ {
+            ObjectType::String(_, bytes) => Some(bytes),
+            _ => None,
+        };
+    }
+
+    pub fn as_name(&self) -> Option<&Name<'static>> {
+        return match &self.typ {
+            ObjectType::Name(name) => Some(name),
+            _ => None,
+        };
+    }
+
+    pub fn as_array(&self) -> Option<&[Object]> {
+        return match &self.typ {
+            ObjectType::Array(children) => Some(children),
+            _ => None,
+        };
+    }
+
+    pub fn as_dict(&self) -> Option<&Dictionary> {
+        return match &self.typ {
+            ObjectType::Dictionary(dict) => Some(dict),
+            ObjectType::Stream(stream) => Some(&stream.dict),
+            _ => None,
+        };
+    }
+
+    pub fn as_stream(&self) -> Option<&Stream> {
+        return match &self.typ {
+            ObjectType::Stream(stream) => Some(stream),
+            _ => None,
+        };
+    }
+
+    pub fn as_reference(&self) -> Option<Reference> {
+        return match self.typ {
+            ObjectType::Reference(reference) => Some(reference),
+            _ => None,
+        };
+    }
+}
+
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        return self.typ == other.typ;
+    }
+}
+
+impl From<ObjectType> for Object {
+    fn from(typ: ObjectType) -> Self {
+        return Object::new(typ, 0, 0);
+    }
+}
+
+impl From<bool> for Object {
+    fn from(value: bool) -> Self {
+        return Object::boolean(value);
+    }
+}
+
+impl From<i64> for Object {
+    fn from(value: i64) -> Self {
+        return Object::integer(value);
+    }
+}
+
+impl From<f64> for Object {
+    fn from(value: f64) -> Self {
+        return Object::real(value);
+    }
+}
+
+impl From<Name<'static>> for Object {
+    fn from(value: Name<'static>) -> Self {
+        return Object::name(value);
+    }
+}
+
+impl From<Vec<Object>> for Object {
+    fn from(value: Vec<Object>) -> Self {
+        return Object::array(value);
+    }
+}
+
+impl From<Dictionary> for Object {
+    fn from(value: Dictionary) -> Self {
+        return Object::dictionary(value);
+    }
+}
+
+impl From<Stream> for Object {
+    fn from(value: Stream) -> Self {
+        return ObjectType::Stream(value).into();
+    }
+}
+
+impl From<Reference> for Object {
+    fn from(value: Reference) -> Self {
+        return ObjectType::Reference(value).into();
+    }
 }
 
 //boolean values, integers, real numbers, strings, names, arrays, dictionaries, streams, and the null object.
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum NumericObjectType {
-    Integer,
-    Real,
+    Integer(i64),
+    Real(f64),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StringObjectType {
     Literal,
     Hexadecimal,
 }
 
+/// A PDF name object (7.3.5), holding the decoded bytes without the leading solidus.
+///
+/// Names are borrowed from the input when no `#xx` escapes need decoding, and compare by their
+/// decoded bytes, so `/Type` read from a file equals `Name::from("Type")`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Name<'a>(Cow<'a, [u8]>);
+
+impl<'a> Name<'a> {
+    pub fn new(bytes: impl Into<Cow<'a, [u8]>>) -> Name<'a> {
+        return Name(bytes.into());
+    }
+
+    /// Decodes the lexeme of a name token, see [`crate::lexer::decode_name`]
+    pub fn from_lexeme(lexeme: &'a [u8]) -> Name<'a> {
+        return Name(crate::lexer::decode_name(lexeme));
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        return &self.0;
+    }
+
+    /// The name as text, if it is valid UTF-8
+    pub fn as_str(&self) -> Option<&str> {
+        return std::str::from_utf8(&self.0).ok();
+    }
+
+    /// Detaches the name from the buffer it was read from
+    pub fn into_owned(self) -> Name<'static> {
+        return Name(Cow::Owned(self.0.into_owned()));
+    }
+}
+
+impl<'a> From<&'a str> for Name<'a> {
+    fn from(value: &'a str) -> Self {
+        return Name(Cow::Borrowed(value.as_bytes()));
+    }
+}
+
+impl<'a> From<&'a [u8]> for Name<'a> {
+    fn from(value: &'a [u8]) -> Self {
+        return Name(Cow::Borrowed(value));
+    }
+}
+
+impl From<Vec<u8>> for Name<'static> {
+    fn from(value: Vec<u8>) -> Self {
+        return Name(Cow::Owned(value));
+    }
+}
+
+impl PartialEq<[u8]> for Name<'_> {
+    fn eq(&self, other: &[u8]) -> bool {
+        return self.as_bytes() == other;
+    }
+}
+
+impl PartialEq<str> for Name<'_> {
+    fn eq(&self, other: &str) -> bool {
+        return self.as_bytes() == other.as_bytes();
+    }
+}
+
+impl PartialEq<&str> for Name<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        return self.as_bytes() == other.as_bytes();
+    }
+}
+
+impl fmt::Display for Name<'_> {
+    /// Writes the name as it would appear in a file, escaping anything that is not a regular
+    /// character
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/")?;
+        for &byte in self.as_bytes() {
+            if (b'!'..=b'~').contains(&byte) && !b"()<>[]{}/%#".contains(&byte) {
+                write!(f, "{}", byte as char)?;
+            } else {
+                write!(f, "#{:02X}", byte)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Identifies an indirect object by its object number and generation number (7.3.10)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Reference {
+    pub num: u32,
+    pub gen: u16,
+}
+
+impl Reference {
+    pub fn new(num: u32, gen: u16) -> Reference {
+        return Reference { num, gen };
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} R", self.num, self.gen)
+    }
+}
+
+/// A dictionary object (7.3.7), mapping name keys to objects in the order they were written.
+///
+/// Dictionaries compare equal when they hold the same entries, in any order.
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    entries: Vec<(Name<'static>, Object)>,
+}
+
+impl Dictionary {
+    pub fn new() -> Dictionary {
+        return Dictionary { entries: vec![] };
+    }
+
+    /// Builder style [`Dictionary::insert`]
+    pub fn with(mut self, key: impl Into<Name<'static>>, value: impl Into<Object>) -> Dictionary {
+        self.insert(key, value);
+        return self;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    /// Sets the value of `key`, returning the previous value if the key was already present. An
+    /// existing key keeps its position.
+    pub fn insert(
+        &mut self,
+        key: impl Into<Name<'static>>,
+        value: impl Into<Object>,
+    ) -> Option<Object> {
+        let key = key.into();
+        let value = value.into();
+        if let Some((_, existing)) = self.entries.iter_mut().find(|(k, _)| *k == key) {
+            return Some(std::mem::replace(existing, value));
+        }
+        self.entries.push((key, value));
+        return None;
+    }
+
+    pub fn remove(&mut self, key: impl AsRef<[u8]>) -> Option<Object> {
+        let position = self.entries.iter().position(|(k, _)| k == key.as_ref())?;
+        return Some(self.entries.remove(position).1);
+    }
+
+    pub fn contains_key(&self, key: impl AsRef<[u8]>) -> bool {
+        return self.get(key).is_some();
+    }
+
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<&Object> {
+        let key = key.as_ref();
+        return self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Name<'static>, &Object)> {
+        return self.entries.iter().map(|(k, v)| (k, v));
+    }
+
+    pub fn get_name(&self, key: impl AsRef<[u8]>) -> Option<&Name<'static>> {
+        return self.get(key)?.as_name();
+    }
+
+    pub fn get_int(&self, key: impl AsRef<[u8]>) -> Option<i64> {
+        return self.get(key)?.as_int();
+    }
+
+    pub fn get_array(&self, key: impl AsRef<[u8]>) -> Option<&[Object]> {
+        return self.get(key)?.as_array();
+    }
+
+    pub fn get_dict(&self, key: impl AsRef<[u8]>) -> Option<&Dictionary> {
+        return self.get(key)?.as_dict();
+    }
+
+    pub fn get_ref(&self, key: impl AsRef<[u8]>) -> Option<Reference> {
+        return self.get(key)?.as_reference();
+    }
+}
+
+impl PartialEq for Dictionary {
+    fn eq(&self, other: &Self) -> bool {
+        return self.len() == other.len()
+            && self.iter().all(|(k, v)| other.get(k.as_bytes()) == Some(v));
+    }
+}
+
+impl<K: Into<Name<'static>>, V: Into<Object>> FromIterator<(K, V)> for Dictionary {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut dict = Dictionary::new();
+        for (key, value) in iter {
+            dict.insert(key, value);
+        }
+        return dict;
+    }
+}
+
+/// A stream object (7.3.8), its dictionary and the data between `stream` and `endstream` as it
+/// appears in the file, filters not applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stream {
+    pub dict: Dictionary,
+    pub data: Vec<u8>,
+}
+
+impl Stream {
+    pub fn new(dict: Dictionary, data: Vec<u8>) -> Stream {
+        return Stream { dict, data };
+    }
+
+    /// Runs the stream data through its filters, see [`filter::decode`]
+    pub fn decode(&self) -> Result<filter::Decoded<'_>, PDFProcessingError> {
+        return filter::decode(
+            &self.data,
+            self.dict.get("Filter"),
+            self.dict.get("DecodeParms"),
+        );
+    }
+
+    /// The stream data with its filters applied, borrowed when the stream has no filters. Image
+    /// data is left encoded with its image codec.
+    pub fn decoded_data(&self) -> Result<Cow<'_, [u8]>, PDFProcessingError> {
+        return Ok(self.decode()?.data);
+    }
+
+    /// The parameters of an image XObject, `None` for other streams
+    pub fn image_params(&self) -> Option<filter::ImageParams> {
+        if self.dict.get_name("Subtype")? != "Image" {
+            return None;
+        }
+        return Some(filter::ImageParams::from_dict(&self.dict));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum ObjectType {
     Boolean(bool),
     Numeric(NumericObjectType),
-    String(StringObjectType),
-    Name,
-    Array,
-    Dictionary,
-    Stream,
+    /// The string's syntax and its decoded bytes
+    String(StringObjectType, Vec<u8>),
+    Name(Name<'static>),
+    Array(Vec<Object>),
+    Dictionary(Dictionary),
+    Stream(Stream),
     Null,
+    Reference(Reference),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_and_compares_objects() {
+        let page = Dictionary::new()
+            .with("Type", Name::from("Page"))
+            .with("Parent", Reference::new(3, 0))
+            .with(
+                "MediaBox",
+                vec![0i64.into(), 0i64.into(), 612.0.into(), 792i64.into()],
+            );
+
+        let reordered: Dictionary = [
+            ("MediaBox", page.get("MediaBox").unwrap().clone()),
+            ("Parent", Object::reference(3, 0)),
+            ("Type", Object::name("Page")),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(page, reordered);
+
+        // Location is metadata, not part of the value
+        let obj = Object::new(ObjectType::Dictionary(page.clone()), 120, 64);
+        assert_eq!(obj, Object::dictionary(reordered));
+        assert_eq!(obj.offset(), 120);
+
+        match obj.typ() {
+            ObjectType::Dictionary(dict) => {
+                assert_eq!(dict.get_name("Type").unwrap(), "Page");
+                assert_eq!(
+                    dict.get_array("MediaBox").unwrap()[2].as_number(),
+                    Some(612.0)
+                );
+                assert_eq!(dict.get_ref("Parent"), Some(Reference::new(3, 0)));
+            }
+            _ => panic!("Expected a dictionary"),
+        }
+        assert_ne!(Object::integer(1), Object::real(1.0));
+    }
 }
@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+
+/// An entry of the cross-reference table (7.5.4), locating an object number's definition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XrefEntry {
+    /// The object is defined at `offset` bytes from the start of the file
+    InUse { offset: usize, gen: u16 },
+    /// The object number is unused, `next` being the next free object number
+    Free { next: u32, gen: u16 },
+    /// The object is the `index`th object of the object stream with object number `stream`
+    /// (7.5.8.3), its generation number being 0
+    Compressed { stream: u32, index: u32 },
+}
+
+impl XrefEntry {
+    pub fn gen(&self) -> u16 {
+        return match *self {
+            XrefEntry::InUse { gen, .. } => gen,
+            XrefEntry::Free { gen, .. } => gen,
+            XrefEntry::Compressed { .. } => 0,
+        };
+    }
+}
+
+/// Cross-reference entries by object number
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct XrefTable {
+    entries: BTreeMap<u32, XrefEntry>,
+}
+
+impl XrefTable {
+    pub fn new() -> XrefTable {
+        return XrefTable {
+            entries: BTreeMap::new(),
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    /// Sets the entry of object number `num`, returning the previous entry if there was one
+    pub fn insert(&mut self, num: u32, entry: XrefEntry) -> Option<XrefEntry> {
+        return self.entries.insert(num, entry);
+    }
+
+    pub fn get(&self, num: u32) -> Option<&XrefEntry> {
+        return self.entries.get(&num);
+    }
+
+    /// Adds the entries of an older cross-reference section that are not already in the table, so
+    /// that entries of the newest section win
+    pub fn merge_older(&mut self, older: XrefTable) {
+        for (num, entry) in older.entries {
+            self.entries.entry(num).or_insert(entry);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &XrefEntry)> {
+        return self.entries.iter().map(|(num, entry)| (*num, entry));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_older_keeps_newer_entries() {
+        let mut newer = XrefTable::new();
+        newer.insert(
+            1,
+            XrefEntry::InUse {
+                offset: 500,
+                gen: 1,
+            },
+        );
+
+        let mut older = XrefTable::new();
+        older.insert(1, XrefEntry::InUse { offset: 17, gen: 0 });
+        older.insert(2, XrefEntry::InUse { offset: 81, gen: 0 });
+
+        newer.merge_older(older);
+        assert_eq!(newer.len(), 2);
+        assert_eq!(
+            newer.get(1),
+            Some(&XrefEntry::InUse {
+                offset: 500,
+                gen: 1
+            })
+        );
+        assert_eq!(newer.get(2), Some(&XrefEntry::InUse { offset: 81, gen: 0 }));
+    }
+}